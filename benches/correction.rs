@@ -1,13 +1,12 @@
 //! Criterion benchmarks for aadc performance testing.
 //!
-//! These benchmarks measure the performance of the aadc binary by invoking
-//! it as a subprocess. This approach tests real-world performance including
-//! process startup, file I/O, and the complete correction pipeline.
-//!
-//! For micro-benchmarks of internal functions, the code would need to be
-//! refactored to expose a library interface.
+//! The `*_file` benchmarks measure the aadc binary by invoking it as a
+//! subprocess, covering process startup, file I/O, and the complete
+//! correction pipeline. The `lib_*` benchmarks call the library
+//! [`Corrector`] directly to isolate the correction engine itself.
 
-use criterion::{Criterion, criterion_group, criterion_main};
+use aadc::{CorrectionOptions, Corrector};
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
 use std::path::PathBuf;
 use std::process::Command;
 
@@ -115,11 +114,45 @@ fn bench_verbose_mode(c: &mut Criterion) {
     });
 }
 
+/// Benchmark the in-process correction engine on the 100-line fixture
+fn bench_lib_medium(c: &mut Criterion) {
+    let input_file = "tests/fixtures/large/100_lines.input.txt";
+
+    let Ok(input) = std::fs::read_to_string(input_file) else {
+        eprintln!("Skipping bench_lib_medium: {} not found", input_file);
+        return;
+    };
+
+    let corrector = Corrector::new(CorrectionOptions::default());
+
+    c.bench_function("lib_medium", |b| {
+        b.iter(|| corrector.correct_str(black_box(&input)))
+    });
+}
+
+/// Benchmark the in-process correction engine on CJK content
+fn bench_lib_cjk(c: &mut Criterion) {
+    let input_file = "tests/fixtures/large/cjk_content.input.txt";
+
+    let Ok(input) = std::fs::read_to_string(input_file) else {
+        eprintln!("Skipping bench_lib_cjk: {} not found", input_file);
+        return;
+    };
+
+    let corrector = Corrector::new(CorrectionOptions::default());
+
+    c.bench_function("lib_cjk", |b| {
+        b.iter(|| corrector.correct_str(black_box(&input)))
+    });
+}
+
 criterion_group!(
     benches,
     bench_small_file,
     bench_medium_file,
     bench_cjk_content,
-    bench_verbose_mode
+    bench_verbose_mode,
+    bench_lib_medium,
+    bench_lib_cjk
 );
 criterion_main!(benches);
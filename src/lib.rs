@@ -0,0 +1,5551 @@
+//! # ASCII Art Diagram Corrector (aadc)
+//!
+//! Library interface to the aadc correction engine. The `aadc` binary is a
+//! thin CLI wrapper over this crate; everything it does to a diagram is
+//! available here without spawning a process.
+//!
+//! ## Quick Start
+//!
+//! ```rust
+//! use aadc::{CorrectionOptions, Corrector};
+//!
+//! let input = "+-------+\n| short|\n| text  |\n+-------+\n";
+//! let corrector = Corrector::new(CorrectionOptions::default());
+//! let correction = corrector.correct_str(input);
+//!
+//! assert!(correction.changed);
+//! assert_eq!(
+//!     correction.output,
+//!     "+-------+\n| short |\n| text  |\n+-------+\n"
+//! );
+//! assert_eq!(correction.report.stats.blocks_found, 1);
+//! ```
+//!
+//! ## Layers
+//!
+//! - [`Corrector`] / [`CorrectionOptions`]: the stable, string-in/string-out
+//!   entry point with a structured [`CorrectionReport`].
+//! - [`find_diagram_blocks`], [`correct_block`], [`analyze_line`] and the
+//!   [`Revision`] type: the building blocks the corrector is made of, for
+//!   callers that want to drive detection and correction themselves.
+
+#![forbid(unsafe_code)]
+#![warn(missing_docs)]
+
+use std::io::{BufRead as _, Read as _};
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Line Range Processing
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// A range of lines to process (1-indexed, inclusive on both ends)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineRange {
+    /// Start line (1-indexed, inclusive)
+    pub start: usize,
+    /// End line (1-indexed, inclusive, usize::MAX means "to end of file")
+    pub end: usize,
+}
+
+/// Parse a single range specification like "10-50", "50-", "-100", or "42"
+fn parse_single_range(s: &str) -> Result<LineRange, String> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err("Empty range specification".to_string());
+    }
+
+    if let Some(dash_pos) = s.find('-') {
+        let (start_str, end_str) = s.split_at(dash_pos);
+        let end_str = &end_str[1..]; // Skip the dash
+
+        let start = if start_str.is_empty() {
+            1 // "-100" means "1-100"
+        } else {
+            start_str
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid start line: '{}'", start_str))?
+        };
+
+        let end = if end_str.is_empty() {
+            usize::MAX // "50-" means "50 to end"
+        } else {
+            end_str
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid end line: '{}'", end_str))?
+        };
+
+        if start == 0 {
+            return Err("Line numbers start at 1, not 0".to_string());
+        }
+
+        if start > end && end != usize::MAX {
+            return Err(format!("Invalid range: start ({}) > end ({})", start, end));
+        }
+
+        Ok(LineRange { start, end })
+    } else {
+        // Single line number
+        let line = s
+            .parse::<usize>()
+            .map_err(|_| format!("Invalid line number: '{}'", s))?;
+
+        if line == 0 {
+            return Err("Line numbers start at 1, not 0".to_string());
+        }
+
+        Ok(LineRange {
+            start: line,
+            end: line,
+        })
+    }
+}
+
+/// Merge overlapping or adjacent ranges
+pub fn merge_ranges(mut ranges: Vec<LineRange>) -> Vec<LineRange> {
+    if ranges.is_empty() {
+        return ranges;
+    }
+
+    // Sort by start position
+    ranges.sort_by_key(|r| r.start);
+
+    let mut merged = Vec::new();
+    let mut current = ranges[0].clone();
+
+    for range in ranges.into_iter().skip(1) {
+        // Check if overlapping or adjacent (allowing for +1 to merge adjacent)
+        if range.start <= current.end.saturating_add(1) {
+            // Merge: extend current range
+            current.end = current.end.max(range.end);
+        } else {
+            // No overlap: push current and start new
+            merged.push(current);
+            current = range;
+        }
+    }
+    merged.push(current);
+
+    merged
+}
+
+/// Parse a line ranges specification like "10-50", "1-100,200-250", "50-"
+pub fn parse_line_ranges(s: &str) -> Result<Vec<LineRange>, String> {
+    let mut ranges = Vec::new();
+
+    for part in s.split(',') {
+        let part = part.trim();
+        if part.is_empty() {
+            continue;
+        }
+        ranges.push(parse_single_range(part)?);
+    }
+
+    if ranges.is_empty() {
+        return Err("No valid ranges specified".to_string());
+    }
+
+    // Merge overlapping ranges
+    Ok(merge_ranges(ranges))
+}
+
+/// Check if a line number (1-indexed) falls within any of the given ranges
+pub fn line_in_ranges(line_num: usize, ranges: &[LineRange]) -> bool {
+    ranges
+        .iter()
+        .any(|r| line_num >= r.start && line_num <= r.end)
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Correction Options and Statistics
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Which lines get their tabs expanded to spaces.
+///
+/// Detection and correction always work on a tab-expanded view of the
+/// input; this only controls which lines are written back expanded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TabExpansion {
+    /// Keep tabs on every line a revision did not modify
+    Preserve,
+    /// Expand tabs on the lines of corrected diagram blocks only
+    #[default]
+    ExpandBlocks,
+    /// Expand tabs on every line of the input
+    ExpandAll,
+}
+
+/// Options controlling detection and correction.
+///
+/// The defaults match the CLI defaults (`--max-iters 10`, `--min-score 0.5`,
+/// `--tab-width 4`). New options may be added in future releases, so build
+/// values from [`CorrectionOptions::default`] and override fields as needed.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct CorrectionOptions {
+    /// Maximum correction iterations per block
+    pub max_iters: usize,
+    /// Minimum score a revision needs to be applied (0.0-1.0)
+    pub min_score: f64,
+    /// Tab expansion width
+    pub tab_width: usize,
+    /// Which lines are written back with tabs expanded
+    pub tabs: TabExpansion,
+    /// Column width of East Asian Ambiguous characters
+    pub ambiguous_width: AmbiguousWidth,
+    /// Process all diagram-like blocks, not just confident ones.
+    /// Also bypasses the quick-scan passthrough.
+    pub all_blocks: bool,
+    /// Only correct blocks overlapping these line ranges (all blocks if `None`)
+    pub lines: Option<Vec<LineRange>>,
+    /// Pull overshooting right borders back by removing surplus spaces,
+    /// aligning to the box's corners (or most common border column) rather
+    /// than the rightmost border. Blocks of side-by-side boxes stay
+    /// insert-only.
+    pub allow_shrink: bool,
+    /// Treat the input as Markdown: only correct inside fenced code blocks
+    /// and pass everything else through untouched
+    pub markdown: bool,
+    /// In Markdown mode, only correct fences whose language (the first word
+    /// of the info string) is one of these words (e.g. `text`, `ascii`,
+    /// `diagram`); all fences if `None`
+    pub fence_info: Option<Vec<String>>,
+    /// Treat the input as source code: only correct inside runs of line
+    /// comments, with the comment prefix stripped while detecting and
+    /// correcting. Ignored when `markdown` is set.
+    pub comments: bool,
+}
+
+impl Default for CorrectionOptions {
+    fn default() -> Self {
+        Self {
+            max_iters: 10,
+            min_score: 0.5,
+            tab_width: 4,
+            tabs: TabExpansion::default(),
+            ambiguous_width: AmbiguousWidth::default(),
+            all_blocks: false,
+            lines: None,
+            allow_shrink: false,
+            markdown: false,
+            fence_info: None,
+            comments: false,
+        }
+    }
+}
+
+/// Statistics collected during correction
+#[derive(Debug, Default, Clone)]
+pub struct Stats {
+    /// Number of diagram blocks detected
+    pub blocks_found: usize,
+    /// Number of blocks that received modifications
+    pub blocks_modified: usize,
+    /// Number of blocks skipped (low confidence or outside line ranges)
+    pub blocks_skipped: usize,
+    /// Total number of revisions applied
+    pub total_revisions: usize,
+    /// Number of revisions skipped (below min_score threshold)
+    pub revisions_skipped: usize,
+    /// Number of applied revisions that removed whitespace rather than
+    /// inserting it (included in `total_revisions`)
+    pub revisions_shrunk: usize,
+    /// Total number of lines processed
+    pub total_lines: usize,
+    /// Processing elapsed time
+    pub elapsed: Duration,
+}
+
+impl Stats {
+    /// Merge another Stats into this one (for aggregating across files)
+    pub fn merge(&mut self, other: &Stats) {
+        self.blocks_found += other.blocks_found;
+        self.blocks_modified += other.blocks_modified;
+        self.blocks_skipped += other.blocks_skipped;
+        self.total_revisions += other.total_revisions;
+        self.revisions_skipped += other.revisions_skipped;
+        self.revisions_shrunk += other.revisions_shrunk;
+        self.total_lines += other.total_lines;
+        self.elapsed += other.elapsed;
+    }
+
+    /// Calculate lines processed per second
+    pub fn lines_per_second(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.total_lines as f64 / secs
+        } else {
+            self.total_lines as f64
+        }
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Quick Scan (Passthrough Optimization)
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Most lines without box characters that can separate two lines of one
+/// candidate region (block detection allows fewer, so no block is split)
+pub const QUICK_SCAN_MAX_GAP: usize = 3;
+
+/// Summary of a quick scan decision for diagram detection.
+///
+/// The scan covers every line of the input, so a diagram deep inside a long
+/// document is never passed through unnoticed.
+#[derive(Debug, Clone, Default)]
+pub struct QuickScanResult {
+    /// Number of lines scanned
+    pub lines_scanned: usize,
+    /// Number of scanned lines containing at least one box-drawing character
+    pub lines_with_box_chars: usize,
+    /// Candidate diagram regions that triggered processing, as line ranges
+    /// of the input
+    pub regions: Vec<Range<usize>>,
+    /// True if there is at least one candidate region
+    pub likely_has_diagrams: bool,
+}
+
+/// Which box-drawing characters a line contains
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BoxContent {
+    None,
+    /// Only fill characters such as `-` or `═`
+    Fill,
+    /// At least one border, corner or junction
+    Border,
+}
+
+/// Find the box-drawing characters in a line with a byte-level scan,
+/// decoding only the non-ASCII characters
+fn box_content(line: &str) -> BoxContent {
+    let mut found = BoxContent::None;
+    for (i, &byte) in line.as_bytes().iter().enumerate() {
+        let c = if byte.is_ascii() {
+            byte as char
+        } else if byte >= 0xC0 {
+            // Lead byte of a multi-byte character
+            line[i..].chars().next().unwrap_or(' ')
+        } else {
+            continue;
+        };
+        if is_border_char(c) {
+            return BoxContent::Border;
+        }
+        if is_box_char(c) {
+            found = BoxContent::Fill;
+        }
+    }
+    found
+}
+
+/// A run of box-character lines being collected by the quick scan
+struct Candidate {
+    lines: Range<usize>,
+    box_lines: usize,
+    has_border: bool,
+}
+
+/// Quickly scan input lines to decide whether full processing is necessary.
+pub fn quick_scan_for_diagrams(lines: &[String]) -> QuickScanResult {
+    quick_scan_regions(lines, std::slice::from_ref(&(0..lines.len())))
+}
+
+/// Quick scan restricted to the given line ranges (fenced code blocks or
+/// comment runs); candidate regions never span two of them.
+///
+/// Lines with box-drawing characters are grouped when at most
+/// [`QUICK_SCAN_MAX_GAP`] other lines separate them. A group is a candidate
+/// region if it has at least two such lines and at least one border,
+/// corner or junction: anything less gives correction nothing to align.
+pub fn quick_scan_regions(lines: &[String], regions: &[Range<usize>]) -> QuickScanResult {
+    let mut scan = QuickScanResult::default();
+
+    for region in regions {
+        let mut current: Option<Candidate> = None;
+        for idx in region.clone() {
+            scan.lines_scanned += 1;
+            let content = box_content(&lines[idx]);
+            if content == BoxContent::None {
+                continue;
+            }
+            scan.lines_with_box_chars += 1;
+
+            match &mut current {
+                Some(candidate) if idx - candidate.lines.end <= QUICK_SCAN_MAX_GAP => {
+                    candidate.lines.end = idx + 1;
+                    candidate.box_lines += 1;
+                    candidate.has_border |= content == BoxContent::Border;
+                }
+                _ => {
+                    scan.push_candidate(current.take());
+                    current = Some(Candidate {
+                        lines: idx..idx + 1,
+                        box_lines: 1,
+                        has_border: content == BoxContent::Border,
+                    });
+                }
+            }
+        }
+        scan.push_candidate(current);
+    }
+
+    scan.likely_has_diagrams = !scan.regions.is_empty();
+    scan
+}
+
+impl QuickScanResult {
+    fn push_candidate(&mut self, candidate: Option<Candidate>) {
+        if let Some(candidate) = candidate {
+            if candidate.box_lines >= 2 && candidate.has_border {
+                self.regions.push(candidate.lines);
+            }
+        }
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Line Classification
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Classification of a line's role in a diagram.
+///
+/// Lines are classified based on the presence and type of box-drawing
+/// characters. This classification drives revision generation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    /// Empty or whitespace-only line.
+    ///
+    /// Blank lines may separate logical sections within a diagram.
+    Blank,
+
+    /// A line with no detected diagram structure.
+    ///
+    /// These lines are passed through unchanged.
+    None,
+
+    /// A line with vertical borders but no horizontal structure.
+    ///
+    /// Weak lines form the content rows of boxes:
+    /// ```text
+    /// | Content  |   ← Weak (vertical borders only)
+    /// │ データ   │   ← Weak (Unicode vertical)
+    /// ```
+    Weak,
+
+    /// A line with strong horizontal structure.
+    ///
+    /// Strong lines typically form the top/bottom borders of boxes:
+    /// ```text
+    /// +----------+   ← Strong (corners + horizontal runs)
+    /// ┌──────────┐   ← Strong (Unicode corners + horizontal)
+    /// ```
+    Strong,
+}
+
+impl LineKind {
+    /// True for lines that carry diagram structure (Weak or Strong)
+    pub fn is_boxy(self) -> bool {
+        matches!(self, Self::Weak | Self::Strong)
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Box Drawing Character Detection
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Check if character is a corner piece (ASCII or Unicode)
+pub fn is_corner(c: char) -> bool {
+    matches!(
+        c,
+        '+'
+            | '┌'..='┛' // Light and heavy, with every weight mix
+            | '╒'..='╝' // Double and single/double mixes
+            | '╭'..='╰' // Rounded
+    )
+}
+
+/// Check if character is a horizontal fill (for borders)
+pub fn is_horizontal_fill(c: char) -> bool {
+    matches!(
+        c,
+        '-' | '~'
+            | '='
+            | '─'
+            | '━'
+            | '═'
+            | '┄'
+            | '┅'
+            | '┈'
+            | '┉'
+            | '╌'
+            | '╍'
+            | '╴'
+            | '╶'
+            | '╸'
+            | '╺'
+            | '╼'
+            | '╾'
+    )
+}
+
+/// Check if character is a vertical border
+pub fn is_vertical_border(c: char) -> bool {
+    matches!(
+        c,
+        '|' | '│'
+            | '┃'
+            | '║'
+            | '┆'
+            | '┇'
+            | '┊'
+            | '┋'
+            | '╎'
+            | '╏'
+            | '╵'
+            | '╷'
+            | '╹'
+            | '╻'
+            | '╽'
+            | '╿'
+    )
+}
+
+/// Check if character is a T-junction or cross
+pub fn is_junction(c: char) -> bool {
+    matches!(c, '├'..='╋' | '╞'..='╬')
+}
+
+/// Check if character could be part of a box drawing: the ASCII border
+/// characters and the whole Box Drawing block (U+2500–U+257F), diagonals
+/// included
+pub fn is_box_char(c: char) -> bool {
+    is_corner(c)
+        || is_horizontal_fill(c)
+        || is_vertical_border(c)
+        || ('\u{2500}'..='\u{257F}').contains(&c)
+}
+
+/// Check if character can terminate a line border
+pub fn is_border_char(c: char) -> bool {
+    is_vertical_border(c) || is_corner(c) || is_junction(c)
+}
+
+/// Detect the most common vertical border character in a set of lines
+pub fn detect_vertical_border(lines: &[&str]) -> char {
+    let mut counts = std::collections::HashMap::new();
+
+    for line in lines {
+        for c in line.chars() {
+            if is_vertical_border(c) {
+                *counts.entry(c).or_insert(0) += 1;
+            }
+        }
+    }
+
+    // Default to ASCII pipe if no Unicode detected
+    counts
+        .into_iter()
+        .max_by_key(|(_, count)| *count)
+        .map(|(c, _)| c)
+        .unwrap_or('|')
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Line Analysis
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Result of analyzing a single line for diagram structure.
+///
+/// Contains extracted properties used for revision generation:
+/// - The line's classification (Strong, Weak, Blank, None)
+/// - Visual width accounting for CJK and other wide characters
+/// - Prefix and suffix border positions and characters if detected
+#[derive(Debug)]
+pub struct AnalyzedLine {
+    /// The original line content (unmodified)
+    pub content: String,
+
+    /// Classification of the line based on box-drawing characters
+    pub kind: LineKind,
+
+    /// Visual width in terminal columns (CJK chars count as 2)
+    pub visual_width: usize,
+
+    /// Number of leading space characters
+    pub indent: usize,
+
+    /// Detected left-side border information, if any
+    pub prefix_border: Option<PrefixBorder>,
+
+    /// Detected right-side border information, if any
+    pub suffix_border: Option<SuffixBorder>,
+}
+
+/// Information about a detected left-side border character.
+///
+/// Used to find a box's left edge (its top-left corner) and to
+/// generate revisions that re-indent drifted rows to match.
+#[derive(Debug, Clone)]
+pub struct PrefixBorder {
+    /// Visual column position where the border appears (0-indexed)
+    pub column: usize,
+
+    /// The actual border character (`|`, `┌`, `├`, etc.)
+    pub char: char,
+
+    /// True if the border is a corner, i.e. the line opens or closes
+    /// a box rather than being one of its rows
+    pub is_corner: bool,
+}
+
+/// Information about a detected right-side border character.
+///
+/// Used to determine the target column for alignment and to
+/// generate revisions that pad lines to match.
+#[derive(Debug, Clone)]
+pub struct SuffixBorder {
+    /// Visual column position where the border appears (0-indexed)
+    pub column: usize,
+
+    /// The actual border character (`|`, `│`, etc.)
+    pub char: char,
+
+    /// True if this appears to be a closing border (end of content),
+    /// false if it's a mid-line separator
+    pub is_closing: bool,
+}
+
+/// How wide East Asian Ambiguous characters are drawn.
+///
+/// Characters such as `→`, `•`, `…`, `°` and Greek or Cyrillic letters
+/// take one column in most terminals but two in terminals configured for
+/// CJK locales. Box drawing characters (the whole U+2500–U+257F block,
+/// including the ambiguous heavy corners `┏┓┗┛`) are always measured as one
+/// column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AmbiguousWidth {
+    /// One column (Western terminals)
+    #[default]
+    Narrow,
+    /// Two columns (CJK terminals)
+    Wide,
+}
+
+/// Calculate the visual width of a single character in terminal columns.
+///
+/// Widths come from the Unicode East Asian Width tables:
+/// - ASCII and box drawing characters: 1 column
+/// - Wide and fullwidth characters (CJK, most emoji): 2 columns
+/// - Combining marks, joiners and variation selectors: 0 columns
+/// - Ambiguous characters: 1 column, or 2 under [`AmbiguousWidth::Wide`]
+///
+/// A character on its own can't account for emoji sequences or combining
+/// marks; measure text with [`visual_width`] instead.
+pub fn char_width(c: char) -> usize {
+    char_width_with(c, AmbiguousWidth::Narrow)
+}
+
+/// [`char_width`] with ambiguous characters measured as `ambiguous`
+pub fn char_width_with(c: char, ambiguous: AmbiguousWidth) -> usize {
+    if c.is_ascii() || is_box_char(c) {
+        return 1;
+    }
+    let width = match ambiguous {
+        AmbiguousWidth::Narrow => c.width(),
+        AmbiguousWidth::Wide => c.width_cjk(),
+    };
+    width.unwrap_or(0)
+}
+
+/// Width of one extended grapheme cluster
+fn grapheme_width(grapheme: &str, ambiguous: AmbiguousWidth) -> usize {
+    let mut chars = grapheme.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => char_width_with(c, ambiguous),
+        (Some(c), Some(_)) if is_box_char(c) => 1,
+        _ => match ambiguous {
+            AmbiguousWidth::Narrow => grapheme.width(),
+            AmbiguousWidth::Wide => grapheme.width_cjk(),
+        },
+    }
+}
+
+/// The grapheme clusters of a line as `(byte index, first char, width)`
+fn clusters(
+    line: &str,
+    ambiguous: AmbiguousWidth,
+) -> impl Iterator<Item = (usize, char, usize)> + '_ {
+    line.grapheme_indices(true).map(move |(idx, grapheme)| {
+        let first = grapheme.chars().next().unwrap_or(' ');
+        (idx, first, grapheme_width(grapheme, ambiguous))
+    })
+}
+
+/// Calculate the visual width of a string in terminal columns.
+///
+/// The string is measured one extended grapheme cluster at a time, so
+/// combining marks, emoji ZWJ sequences and variation selectors count as
+/// the single glyph a terminal draws:
+///
+/// ```text
+/// visual_width("Hello")     == 5   // ASCII only
+/// visual_width("你好")      == 4   // CJK (2 chars × 2 columns)
+/// visual_width("e\u{301}")  == 1   // e + combining acute accent
+/// visual_width("a → b")     == 5   // ambiguous arrow, narrow by default
+/// ```
+///
+/// This is critical for correct padding calculations in diagrams.
+pub fn visual_width(s: &str) -> usize {
+    visual_width_with(s, AmbiguousWidth::Narrow)
+}
+
+/// [`visual_width`] with ambiguous characters measured as `ambiguous`
+pub fn visual_width_with(s: &str, ambiguous: AmbiguousWidth) -> usize {
+    s.graphemes(true)
+        .map(|g| grapheme_width(g, ambiguous))
+        .sum()
+}
+
+/// The evidence [`classify_line`] weighs, as returned by [`explain_line`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineEvidence {
+    /// The resulting classification
+    pub kind: LineKind,
+    /// Box-drawing characters in the trimmed line
+    pub box_chars: usize,
+    /// All characters in the trimmed line
+    pub total_chars: usize,
+    /// The line contains a corner (`+`, `┌`, `╝`, ...)
+    pub has_corner: bool,
+    /// The trimmed line starts with a border character
+    pub starts_with_border: bool,
+    /// The trimmed line ends with a border character
+    pub ends_with_border: bool,
+}
+
+impl LineEvidence {
+    /// True if at least a third of the line is box-drawing characters
+    pub fn mostly_box_chars(&self) -> bool {
+        self.box_chars > 0 && self.box_chars * 3 >= self.total_chars
+    }
+}
+
+/// Classify a single line
+pub fn classify_line(line: &str) -> LineKind {
+    explain_line(line).kind
+}
+
+/// Classify a single line, returning the evidence behind the decision.
+///
+/// A line is [`LineKind::Strong`] if it has a corner, starts and ends with
+/// a border character, or is at least a third box-drawing characters;
+/// otherwise any box-drawing character makes it [`LineKind::Weak`].
+pub fn explain_line(line: &str) -> LineEvidence {
+    let trimmed = line.trim();
+    let mut evidence = LineEvidence {
+        kind: LineKind::Blank,
+        box_chars: trimmed.chars().filter(|&c| is_box_char(c)).count(),
+        total_chars: trimmed.chars().count(),
+        has_corner: false,
+        starts_with_border: false,
+        ends_with_border: false,
+    };
+
+    if trimmed.is_empty() {
+        return evidence;
+    }
+    if evidence.box_chars == 0 {
+        evidence.kind = LineKind::None;
+        return evidence;
+    }
+
+    // Check for strong indicators
+    evidence.has_corner = trimmed.chars().any(is_corner);
+    evidence.starts_with_border = trimmed.chars().next().is_some_and(is_border_char);
+    evidence.ends_with_border = trimmed.chars().next_back().is_some_and(is_border_char);
+
+    // Strong: has corners, or starts AND ends with border chars, or high ratio
+    evidence.kind = if evidence.has_corner
+        || (evidence.starts_with_border && evidence.ends_with_border)
+        || evidence.mostly_box_chars()
+    {
+        LineKind::Strong
+    } else {
+        LineKind::Weak
+    };
+    evidence
+}
+
+/// Analyze a line for correction
+pub fn analyze_line(line: &str) -> AnalyzedLine {
+    analyze_line_with(line, AmbiguousWidth::Narrow)
+}
+
+/// [`analyze_line`] with ambiguous characters measured as `ambiguous`
+pub fn analyze_line_with(line: &str, ambiguous: AmbiguousWidth) -> AnalyzedLine {
+    let kind = classify_line(line);
+    let visual = visual_width_with(line, ambiguous);
+    let indent = line.len() - line.trim_start().len();
+
+    // Detect prefix and suffix borders
+    let (prefix_border, suffix_border) = if kind.is_boxy() {
+        (
+            detect_prefix_border(line),
+            detect_suffix_border_with(line, ambiguous),
+        )
+    } else {
+        (None, None)
+    };
+
+    AnalyzedLine {
+        content: line.to_string(),
+        kind,
+        visual_width: visual,
+        indent,
+        prefix_border,
+        suffix_border,
+    }
+}
+
+/// Detect a left-side border in a line
+pub fn detect_prefix_border(line: &str) -> Option<PrefixBorder> {
+    let trimmed = line.trim_start();
+    let first_char = trimmed.chars().next()?;
+
+    if is_border_char(first_char) {
+        let leading = &line[..line.len() - trimmed.len()];
+        Some(PrefixBorder {
+            column: visual_width(leading),
+            char: first_char,
+            is_corner: is_corner(first_char),
+        })
+    } else {
+        None
+    }
+}
+
+/// Detect a right-side border in a line
+pub fn detect_suffix_border(line: &str) -> Option<SuffixBorder> {
+    detect_suffix_border_with(line, AmbiguousWidth::Narrow)
+}
+
+/// [`detect_suffix_border`] with ambiguous characters measured as `ambiguous`
+pub fn detect_suffix_border_with(line: &str, ambiguous: AmbiguousWidth) -> Option<SuffixBorder> {
+    let trimmed = line.trim_end();
+    if trimmed.is_empty() {
+        return None;
+    }
+
+    let last_char = trimmed.chars().next_back()?;
+
+    if is_border_char(last_char) {
+        let prefix = &trimmed[..trimmed.len() - last_char.len_utf8()];
+        let column = visual_width_with(prefix, ambiguous);
+        Some(SuffixBorder {
+            column,
+            char: last_char,
+            is_closing: is_corner(last_char) || is_junction(last_char),
+        })
+    } else {
+        None
+    }
+}
+
+/// Detect the fill character of a horizontal rule: the character just
+/// before the suffix border, if it is a horizontal fill (`+-----+` → `-`)
+pub fn detect_rule_fill(line: &str) -> Option<char> {
+    let trimmed = line.trim_end();
+    let mut chars = trimmed.chars().rev();
+    let last_char = chars.next()?;
+    let before = chars.next()?;
+
+    (is_border_char(last_char) && is_horizontal_fill(before)).then_some(before)
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Inline Directives
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// An inline `aadc:` directive, written in a comment on a line of its own.
+///
+/// ```text
+/// <!-- aadc: ignore-next-block -->
+/// // aadc: force, min-score=0.3
+/// # aadc: off
+/// -- aadc: on
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Directive {
+    /// `off`: leave every line alone until `aadc: on`
+    Off,
+    /// `on`: end an `aadc: off` region
+    On,
+    /// `ignore-next-block`: leave the next block alone
+    IgnoreNextBlock,
+    /// `force`: correct the next block whatever its confidence
+    Force,
+    /// `min-score=N`: use `N` as the next block's `min_score`
+    MinScore(f64),
+}
+
+/// Parse the directives on a line.
+///
+/// The line must be nothing but a comment (`<!-- ... -->`, `--`, or any of
+/// the line comment leaders `--comments` knows: `//`, `///`, `//!`, `#`,
+/// or the `*` of a `/* */` continuation line) whose text starts with
+/// `aadc:`, followed by one or more directives separated by commas or
+/// spaces. Any other line, including one with an unknown directive, yields
+/// an empty list.
+pub fn parse_directives(line: &str) -> Vec<Directive> {
+    let trimmed = line.trim();
+    let comment = trimmed
+        .strip_prefix("<!--")
+        .and_then(|rest| rest.strip_suffix("-->"))
+        .or_else(|| split_comment(trimmed).map(|(_, text)| text))
+        .or_else(|| trimmed.strip_prefix("--"));
+    let Some(spec) = comment.and_then(|text| text.trim().strip_prefix("aadc:")) else {
+        return Vec::new();
+    };
+
+    let mut directives = Vec::new();
+    for word in spec
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|word| !word.is_empty())
+    {
+        let directive = match word {
+            "off" => Directive::Off,
+            "on" => Directive::On,
+            "ignore-next-block" => Directive::IgnoreNextBlock,
+            "force" => Directive::Force,
+            _ => match word
+                .strip_prefix("min-score=")
+                .and_then(|score| score.parse::<f64>().ok())
+            {
+                Some(score) if (0.0..=1.0).contains(&score) => Directive::MinScore(score),
+                _ => return Vec::new(),
+            },
+        };
+        directives.push(directive);
+    }
+    directives
+}
+
+/// Directive state carried through a document by
+/// [`find_diagram_blocks_with`]
+#[derive(Debug, Clone, Default)]
+pub struct Directives {
+    /// Inside an `aadc: off` region
+    pub off: bool,
+    /// The next block is left alone (`ignore-next-block`)
+    pub ignore_next: bool,
+    /// The next block is kept whatever its confidence (`force`)
+    pub force_next: bool,
+    /// `min_score` for the next block (`min-score=N`)
+    pub next_min_score: Option<f64>,
+}
+
+impl Directives {
+    /// Apply directives in order
+    pub fn apply(&mut self, directives: &[Directive]) {
+        for directive in directives {
+            match *directive {
+                Directive::Off => self.off = true,
+                Directive::On => self.off = false,
+                Directive::IgnoreNextBlock => self.ignore_next = true,
+                Directive::Force => self.force_next = true,
+                Directive::MinScore(score) => self.next_min_score = Some(score),
+            }
+        }
+    }
+
+    /// Apply the directives on `line`, if any
+    pub fn apply_line(&mut self, line: &str) {
+        self.apply(&parse_directives(line));
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Diagram Block Detection
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// A detected ASCII diagram block within the input text.
+///
+/// Blocks are identified by consecutive lines containing box-drawing
+/// characters. Each block is processed independently by the correction
+/// algorithm.
+///
+/// # Confidence Scoring
+///
+/// The confidence score (0.0-1.0) indicates how likely this block is
+/// to be an actual diagram versus coincidental box characters:
+/// - 0.9-1.0: Very likely a diagram (multiple strong lines)
+/// - 0.5-0.9: Probably a diagram (mixed strong/weak lines)
+/// - 0.0-0.5: Uncertain (weak lines only, may be table or code)
+#[derive(Debug, Clone)]
+pub struct DiagramBlock {
+    /// Starting line index in the input (0-based, inclusive)
+    pub start: usize,
+
+    /// Ending line index in the input (exclusive)
+    pub end: usize,
+
+    /// Confidence that this is an actual diagram (0.0-1.0)
+    pub confidence: f64,
+
+    /// Kept whatever its confidence, by an `aadc: force` directive
+    pub forced: bool,
+
+    /// `min_score` for this block, from an `aadc: min-score=N` directive
+    pub min_score: Option<f64>,
+}
+
+/// Blocks scoring below this confidence are ignored unless
+/// [`CorrectionOptions::all_blocks`] is set
+pub const MIN_BLOCK_CONFIDENCE: f64 = 0.3;
+
+/// How a block's detection confidence is computed: 0.8 × the share of its
+/// boxy lines that are strong, plus 0.02 per line up to 0.2
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlockConfidence {
+    /// Strong lines in the block
+    pub strong: usize,
+    /// Weak lines in the block
+    pub weak: usize,
+    /// `strong / (strong + weak)`
+    pub strong_ratio: f64,
+    /// Bonus for taller blocks (0.0-0.2)
+    pub size_bonus: f64,
+    /// The resulting confidence (0.0-1.0)
+    pub confidence: f64,
+}
+
+impl BlockConfidence {
+    /// Confidence for a block of `height` lines with the given counts
+    pub fn new(strong: usize, weak: usize, height: usize) -> Self {
+        let total = strong + weak;
+        if total == 0 {
+            return Self {
+                strong,
+                weak,
+                strong_ratio: 0.0,
+                size_bonus: 0.0,
+                confidence: 0.0,
+            };
+        }
+        let strong_ratio = strong as f64 / total as f64;
+        let size_bonus = (height as f64 / 10.0).min(0.2);
+        Self {
+            strong,
+            weak,
+            strong_ratio,
+            size_bonus,
+            confidence: (strong_ratio * 0.8 + size_bonus).min(1.0),
+        }
+    }
+
+    /// Recount the confidence of a block found in `lines` by
+    /// [`find_diagram_blocks`]
+    pub fn of(lines: &[String], block: &DiagramBlock) -> Self {
+        let kinds: Vec<_> = lines[block.start..block.end]
+            .iter()
+            .map(|line| classify_line(line))
+            .collect();
+        let count = |kind| kinds.iter().filter(|&&k| k == kind).count();
+        Self::new(
+            count(LineKind::Strong),
+            count(LineKind::Weak),
+            block.end - block.start,
+        )
+    }
+}
+
+/// Find diagram blocks in the input text.
+///
+/// Scans the input for consecutive lines containing box-drawing characters
+/// and groups them into blocks. Uses lookahead to merge blocks separated
+/// by single blank lines. Inline directives ([`parse_directives`]) are
+/// honored; a directive line always ends a block.
+pub fn find_diagram_blocks(lines: &[String], all_blocks: bool) -> Vec<DiagramBlock> {
+    find_diagram_blocks_with(lines, all_blocks, &mut Directives::default())
+}
+
+/// [`find_diagram_blocks`], starting from and updating `directives`, so
+/// that a document can be searched in pieces (Markdown fences) with
+/// directives in between still taking effect.
+pub fn find_diagram_blocks_with(
+    lines: &[String],
+    all_blocks: bool,
+    directives: &mut Directives,
+) -> Vec<DiagramBlock> {
+    let parsed: Vec<Vec<Directive>> = lines.iter().map(|line| parse_directives(line)).collect();
+    let kinds: Vec<LineKind> = lines
+        .iter()
+        .zip(&parsed)
+        .map(|(line, found)| {
+            if found.is_empty() {
+                classify_line(line)
+            } else {
+                LineKind::None
+            }
+        })
+        .collect();
+
+    let mut blocks = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        if !parsed[i].is_empty() {
+            directives.apply(&parsed[i]);
+            i += 1;
+            continue;
+        }
+
+        // Skip blank/non-boxy lines
+        let kind = kinds[i];
+        if directives.off || !kind.is_boxy() {
+            i += 1;
+            continue;
+        }
+
+        // Found potential start of a block
+        let start = i;
+        let mut end = i + 1;
+        let mut strong_count = if kind == LineKind::Strong { 1 } else { 0 };
+        let mut weak_count = if kind == LineKind::Weak { 1 } else { 0 };
+        let mut blank_gap = 0;
+
+        // Extend block, up to the next directive
+        while end < lines.len() && parsed[end].is_empty() {
+            match kinds[end] {
+                LineKind::Strong => {
+                    strong_count += 1;
+                    blank_gap = 0;
+                    end += 1;
+                }
+                LineKind::Weak => {
+                    weak_count += 1;
+                    blank_gap = 0;
+                    end += 1;
+                }
+                LineKind::Blank => {
+                    // Allow small gaps within diagrams
+                    blank_gap += 1;
+                    if blank_gap > 1 {
+                        break;
+                    }
+                    end += 1;
+                }
+                LineKind::None => {
+                    // Check if next non-blank is boxy
+                    let lookahead = kinds.iter().skip(end).take(3).any(|k| k.is_boxy());
+                    if lookahead && blank_gap == 0 {
+                        end += 1;
+                    } else {
+                        break;
+                    }
+                }
+            }
+        }
+
+        // Trim trailing blanks
+        while end > start && kinds[end - 1] == LineKind::Blank {
+            end -= 1;
+        }
+
+        let confidence = BlockConfidence::new(strong_count, weak_count, end - start).confidence;
+        let forced = std::mem::take(&mut directives.force_next);
+        let min_score = directives.next_min_score.take();
+
+        // Add block if confidence meets threshold
+        let ignored = std::mem::take(&mut directives.ignore_next);
+        if !ignored && (all_blocks || forced || confidence >= MIN_BLOCK_CONFIDENCE) {
+            blocks.push(DiagramBlock {
+                start,
+                end,
+                confidence,
+                forced,
+                min_score,
+            });
+        }
+
+        i = end;
+    }
+
+    blocks
+}
+
+/// The columns occupied by one box in a row of side-by-side boxes.
+///
+/// Boxes that overlap horizontally (nested boxes, or boxes stacked above
+/// one another) share a single region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoxRegion {
+    /// Visual column of the region's leftmost corner
+    pub left: usize,
+
+    /// Visual column of the region's rightmost corner
+    pub right: usize,
+}
+
+/// Segment a block into column regions, one per side-by-side box.
+///
+/// Regions come from horizontal rules (`+-----+`, `└─────┘`), the one part
+/// of a box whose extent is unambiguous even when its rows have drifted.
+/// Rules that overlap are merged into one region. Returns regions sorted
+/// left to right; a block with fewer than two regions is aligned as a whole.
+pub fn find_box_regions(lines: &[&str]) -> Vec<BoxRegion> {
+    find_box_regions_with(lines, AmbiguousWidth::Narrow)
+}
+
+/// [`find_box_regions`] with ambiguous characters measured as `ambiguous`
+pub fn find_box_regions_with(lines: &[&str], ambiguous: AmbiguousWidth) -> Vec<BoxRegion> {
+    let mut runs: Vec<BoxRegion> = lines.iter().flat_map(|l| rule_runs(l, ambiguous)).collect();
+    runs.sort_by_key(|r| r.left);
+
+    let mut regions: Vec<BoxRegion> = Vec::new();
+    for run in runs {
+        match regions.last_mut() {
+            Some(last) if run.left <= last.right => last.right = last.right.max(run.right),
+            _ => regions.push(run),
+        }
+    }
+    regions
+}
+
+/// Find the horizontal rules on a line: unbroken runs of fill characters
+/// between two corners or junctions (`+---+---+` is a single run)
+fn rule_runs(line: &str, ambiguous: AmbiguousWidth) -> Vec<BoxRegion> {
+    let mut runs = Vec::new();
+    // (start column, column of the last corner/junction, seen a fill)
+    let mut run: Option<(usize, usize, bool)> = None;
+    let mut column = 0;
+
+    for (_, c, width) in clusters(line, ambiguous).chain(std::iter::once((line.len(), ' ', 1))) {
+        if is_corner(c) || is_junction(c) {
+            match &mut run {
+                Some((_, end, _)) => *end = column,
+                None => run = Some((column, column, false)),
+            }
+        } else if is_horizontal_fill(c) {
+            if let Some((_, _, has_fill)) = &mut run {
+                *has_fill = true;
+            }
+        } else if let Some((left, right, has_fill)) = run.take() {
+            if has_fill && right > left {
+                runs.push(BoxRegion { left, right });
+            }
+        }
+        column += width;
+    }
+    runs
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Revision System
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// A proposed modification to align a line's left or right border.
+///
+/// Revisions are generated during the correction loop and scored for
+/// confidence. Only revisions above the `--min-score` threshold are applied.
+///
+/// # Scoring
+///
+/// Each revision type has different base confidence scores:
+/// - `PadBeforeSuffixBorder`: Higher confidence (0.3-0.9), as we're just adding
+///   whitespace before an existing border
+/// - `AddSuffixBorder`: Lower confidence (0.3-0.6), as we're adding a character
+///   that wasn't there
+/// - `ExtendHorizontalRule`: High confidence (0.5-0.85); lengthening a
+///   border run with its own fill character is what the author would have
+///   typed, so larger extensions are penalized less than padding is
+/// - `PadBeforeInteriorBorder`: Scored like padding or rule extension,
+///   depending on whether it inserts spaces or a fill character
+/// - `RemoveSpacesBeforeSuffixBorder`: Scored like padding, less 0.1, since
+///   an overshooting border is occasionally deliberate
+/// - `AlignPrefixBorder`: 0.9 for a one-column drift, dropping by 0.15 per
+///   extra column, since large shifts are more likely intentional
+///
+/// # Monotone Edits
+///
+/// The right-border revisions are "monotone" (insert-only), except for
+/// the opt-in `RemoveSpacesBeforeSuffixBorder`, which only deletes surplus
+/// whitespace. Re-aligning a left border only rewrites the line's leading
+/// whitespace. None of them remove content from the line, making them safe
+/// to apply.
+#[derive(Debug, Clone)]
+pub enum Revision {
+    /// Insert spaces before an existing suffix border to align it.
+    ///
+    /// This is the most common revision type and has higher confidence
+    /// since we're only adjusting whitespace.
+    PadBeforeSuffixBorder {
+        /// Global line index (0-based)
+        line_idx: usize,
+        /// Number of space characters to insert
+        spaces_to_add: usize,
+        /// Target visual column for alignment
+        target_column: usize,
+    },
+
+    /// Lengthen a horizontal rule by repeating its fill character before
+    /// the closing corner or junction.
+    ///
+    /// Used instead of `PadBeforeSuffixBorder` on Strong lines such as
+    /// `+------+` or `└──────┘`, so the rule grows continuously rather than
+    /// becoming `+------   +`.
+    ExtendHorizontalRule {
+        /// Global line index (0-based)
+        line_idx: usize,
+        /// Fill character to repeat (`-`, `─`, `═`, `━`, etc.)
+        fill_char: char,
+        /// Number of fill characters to insert
+        chars_to_add: usize,
+        /// Target visual column for alignment
+        target_column: usize,
+    },
+
+    /// Insert padding before an interior right border: the closing border
+    /// of a box that has another box to its right on the same line.
+    ///
+    /// Pads with spaces on content rows and with the rule's fill character
+    /// on horizontal rules, mirroring `PadBeforeSuffixBorder` and
+    /// `ExtendHorizontalRule` for borders that are not the last character.
+    PadBeforeInteriorBorder {
+        /// Global line index (0-based)
+        line_idx: usize,
+        /// Current visual column of the interior border
+        border_column: usize,
+        /// Character to insert (a space, or the rule's fill character)
+        fill_char: char,
+        /// Number of characters to insert
+        chars_to_add: usize,
+        /// Target visual column for alignment
+        target_column: usize,
+    },
+
+    /// Remove surplus spaces before a suffix border that overshoots the
+    /// target column.
+    ///
+    /// Only generated with `allow_shrink`. Only spaces between the content
+    /// and the border are removed, and at least one is always kept.
+    RemoveSpacesBeforeSuffixBorder {
+        /// Global line index (0-based)
+        line_idx: usize,
+        /// Number of space characters to remove
+        spaces_to_remove: usize,
+        /// Target visual column for alignment
+        target_column: usize,
+    },
+
+    /// Add a border character at the target column.
+    ///
+    /// Used when a line has content but no closing border. Lower confidence
+    /// since we're adding structure that may not be intended.
+    AddSuffixBorder {
+        /// Global line index (0-based)
+        line_idx: usize,
+        /// Border character to add (`|`, `│`, etc.)
+        border_char: char,
+        /// Target visual column for the new border
+        target_column: usize,
+    },
+
+    /// Re-indent a box row so its left border sits under the box's
+    /// top-left corner.
+    ///
+    /// Used when a row's left border has drifted (copy/paste, re-indenting).
+    /// Only the leading whitespace of the line is rewritten.
+    AlignPrefixBorder {
+        /// Global line index (0-based)
+        line_idx: usize,
+        /// Current visual column of the left border
+        current_column: usize,
+        /// Visual column of the box's top-left corner
+        target_column: usize,
+    },
+}
+
+impl Revision {
+    /// Score this revision (higher = more confident it's correct)
+    /// `block_start` is the offset of the block in the global lines array
+    pub fn score(&self, analyzed: &[AnalyzedLine], block_start: usize) -> f64 {
+        let sum: f64 = self
+            .score_terms(analyzed, block_start)
+            .iter()
+            .map(|term| term.value)
+            .sum();
+        match self {
+            Self::AlignPrefixBorder { .. } => sum.clamp(0.0, 0.9),
+            _ => sum,
+        }
+    }
+
+    /// The terms [`Revision::score`] adds up, in order. Only
+    /// `AlignPrefixBorder` clamps the sum (to 0.0-0.9).
+    pub fn score_terms(&self, analyzed: &[AnalyzedLine], block_start: usize) -> Vec<ScoreTerm> {
+        match self {
+            Self::PadBeforeSuffixBorder {
+                line_idx,
+                spaces_to_add,
+                ..
+            } => pad_terms(&analyzed[line_idx - block_start], *spaces_to_add),
+            Self::AddSuffixBorder { line_idx, .. } => {
+                let local_idx = line_idx - block_start;
+                let line = &analyzed[local_idx];
+                // Adding borders is less confident
+                let strength_bonus = if line.kind == LineKind::Strong {
+                    ScoreTerm::new("strong line bonus", 0.2)
+                } else {
+                    ScoreTerm::new("weak line bonus", 0.1)
+                };
+                vec![ScoreTerm::new("base", 0.5), strength_bonus]
+            }
+            Self::ExtendHorizontalRule { chars_to_add, .. } => extend_terms(*chars_to_add),
+            Self::RemoveSpacesBeforeSuffixBorder {
+                line_idx,
+                spaces_to_remove,
+                ..
+            } => {
+                let mut terms = pad_terms(&analyzed[line_idx - block_start], *spaces_to_remove);
+                terms.push(ScoreTerm::new("shrink penalty", -0.1));
+                terms
+            }
+            Self::PadBeforeInteriorBorder {
+                line_idx,
+                fill_char,
+                chars_to_add,
+                ..
+            } => {
+                if *fill_char == ' ' {
+                    pad_terms(&analyzed[line_idx - block_start], *chars_to_add)
+                } else {
+                    extend_terms(*chars_to_add)
+                }
+            }
+            Self::AlignPrefixBorder {
+                current_column,
+                target_column,
+                ..
+            } => {
+                // Small drifts are almost certainly accidental
+                let shift = current_column.abs_diff(*target_column);
+                vec![
+                    ScoreTerm::new("base", 1.05),
+                    ScoreTerm::new("drift penalty", -(shift as f64 * 0.15)),
+                ]
+            }
+        }
+    }
+
+    /// Apply this revision to the lines
+    pub fn apply(&self, lines: &mut [String]) {
+        self.apply_with(lines, AmbiguousWidth::Narrow)
+    }
+
+    /// [`Revision::apply`] with ambiguous characters measured as `ambiguous`
+    pub fn apply_with(&self, lines: &mut [String], ambiguous: AmbiguousWidth) {
+        match self {
+            Self::PadBeforeSuffixBorder {
+                line_idx,
+                spaces_to_add,
+                ..
+            } => {
+                let line = &mut lines[*line_idx];
+                let trimmed = line.trim_end();
+                if let Some(last_char) = trimmed.chars().next_back() {
+                    if is_border_char(last_char) {
+                        // Insert spaces before the last character
+                        let prefix = &trimmed[..trimmed.len() - last_char.len_utf8()];
+                        *line = format!("{}{}{}", prefix, " ".repeat(*spaces_to_add), last_char);
+                    }
+                }
+            }
+            Self::ExtendHorizontalRule {
+                line_idx,
+                fill_char,
+                chars_to_add,
+                ..
+            } => {
+                let line = &mut lines[*line_idx];
+                let trimmed = line.trim_end();
+                if let Some(last_char) = trimmed.chars().next_back() {
+                    if is_border_char(last_char) {
+                        // Insert fill characters before the closing corner
+                        let prefix = &trimmed[..trimmed.len() - last_char.len_utf8()];
+                        let fill: String = std::iter::repeat_n(*fill_char, *chars_to_add).collect();
+                        *line = format!("{}{}{}", prefix, fill, last_char);
+                    }
+                }
+            }
+            Self::RemoveSpacesBeforeSuffixBorder {
+                line_idx,
+                spaces_to_remove,
+                ..
+            } => {
+                let line = &mut lines[*line_idx];
+                let trimmed = line.trim_end();
+                if let Some(last_char) = trimmed.chars().next_back() {
+                    if is_border_char(last_char) {
+                        // Remove spaces before the last character, keeping one
+                        let prefix = &trimmed[..trimmed.len() - last_char.len_utf8()];
+                        let surplus = prefix.len() - prefix.trim_end_matches(' ').len();
+                        if surplus > *spaces_to_remove {
+                            let kept = &prefix[..prefix.len() - spaces_to_remove];
+                            *line = format!("{}{}", kept, last_char);
+                        }
+                    }
+                }
+            }
+            Self::PadBeforeInteriorBorder {
+                line_idx,
+                border_column,
+                fill_char,
+                chars_to_add,
+                ..
+            } => {
+                let line = &mut lines[*line_idx];
+                let mut column = 0;
+                let border = clusters(line, ambiguous).find(|&(_, _, width)| {
+                    let at = column;
+                    column += width;
+                    at == *border_column
+                });
+                if let Some((idx, c, _)) = border {
+                    if is_border_char(c) {
+                        let fill: String = std::iter::repeat_n(*fill_char, *chars_to_add).collect();
+                        line.insert_str(idx, &fill);
+                    }
+                }
+            }
+            Self::AddSuffixBorder {
+                line_idx,
+                border_char,
+                target_column,
+            } => {
+                let line = &mut lines[*line_idx];
+                let current_width = visual_width_with(line.trim_end(), ambiguous);
+                let padding = target_column.saturating_sub(current_width);
+                *line = format!("{}{}{}", line.trim_end(), " ".repeat(padding), border_char);
+            }
+            Self::AlignPrefixBorder {
+                line_idx,
+                target_column,
+                ..
+            } => {
+                let line = &mut lines[*line_idx];
+                *line = format!("{}{}", " ".repeat(*target_column), line.trim_start());
+            }
+        }
+    }
+
+    /// Global line index (0-based) of the line this revision edits
+    pub fn line_idx(&self) -> usize {
+        match self {
+            Self::PadBeforeSuffixBorder { line_idx, .. }
+            | Self::ExtendHorizontalRule { line_idx, .. }
+            | Self::PadBeforeInteriorBorder { line_idx, .. }
+            | Self::RemoveSpacesBeforeSuffixBorder { line_idx, .. }
+            | Self::AddSuffixBorder { line_idx, .. }
+            | Self::AlignPrefixBorder { line_idx, .. } => *line_idx,
+        }
+    }
+
+    /// Visual column this revision aligns a border to
+    pub fn target_column(&self) -> usize {
+        match self {
+            Self::PadBeforeSuffixBorder { target_column, .. }
+            | Self::ExtendHorizontalRule { target_column, .. }
+            | Self::PadBeforeInteriorBorder { target_column, .. }
+            | Self::RemoveSpacesBeforeSuffixBorder { target_column, .. }
+            | Self::AddSuffixBorder { target_column, .. }
+            | Self::AlignPrefixBorder { target_column, .. } => *target_column,
+        }
+    }
+
+    /// Name of the revision variant (`"PadBeforeSuffixBorder"`, ...)
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::PadBeforeSuffixBorder { .. } => "PadBeforeSuffixBorder",
+            Self::ExtendHorizontalRule { .. } => "ExtendHorizontalRule",
+            Self::PadBeforeInteriorBorder { .. } => "PadBeforeInteriorBorder",
+            Self::RemoveSpacesBeforeSuffixBorder { .. } => "RemoveSpacesBeforeSuffixBorder",
+            Self::AddSuffixBorder { .. } => "AddSuffixBorder",
+            Self::AlignPrefixBorder { .. } => "AlignPrefixBorder",
+        }
+    }
+
+    /// Move every column this revision refers to right by `by` (used to
+    /// map columns in a comment's payload back to the full line)
+    fn shift_columns(&mut self, by: usize) {
+        match self {
+            Self::PadBeforeSuffixBorder { target_column, .. }
+            | Self::ExtendHorizontalRule { target_column, .. }
+            | Self::RemoveSpacesBeforeSuffixBorder { target_column, .. }
+            | Self::AddSuffixBorder { target_column, .. } => *target_column += by,
+            Self::PadBeforeInteriorBorder {
+                border_column,
+                target_column,
+                ..
+            } => {
+                *border_column += by;
+                *target_column += by;
+            }
+            Self::AlignPrefixBorder {
+                current_column,
+                target_column,
+                ..
+            } => {
+                *current_column += by;
+                *target_column += by;
+            }
+        }
+    }
+}
+
+/// One named contribution to a revision's score
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoreTerm {
+    /// What the term measures ("base", "size penalty", ...)
+    pub label: &'static str,
+    /// Its contribution (negative for penalties)
+    pub value: f64,
+}
+
+impl ScoreTerm {
+    fn new(label: &'static str, value: f64) -> Self {
+        Self { label, value }
+    }
+}
+
+/// Score terms for padding a border with spaces
+fn pad_terms(line: &AnalyzedLine, spaces_to_add: usize) -> Vec<ScoreTerm> {
+    // Prefer smaller adjustments
+    let adjustment_penalty = (spaces_to_add as f64 / 10.0).min(0.5);
+    let mut terms = vec![
+        ScoreTerm::new("base", 0.8),
+        ScoreTerm::new("size penalty", -adjustment_penalty),
+    ];
+    // Prefer strong lines
+    if line.kind == LineKind::Strong {
+        terms.push(ScoreTerm::new("strong line bonus", 0.2));
+    }
+    terms
+}
+
+/// Score terms for lengthening a horizontal rule with its fill character
+fn extend_terms(chars_to_add: usize) -> Vec<ScoreTerm> {
+    // A rule only ever grows in its own style, so even long
+    // extensions stay fairly safe
+    let adjustment_penalty = (chars_to_add as f64 / 20.0).min(0.35);
+    vec![
+        ScoreTerm::new("base", 0.85),
+        ScoreTerm::new("size penalty", -adjustment_penalty),
+    ]
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Block Correction
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Result of correcting a single block
+#[derive(Debug, Clone, Default)]
+pub struct BlockCorrectionResult {
+    /// Number of revisions applied
+    pub revisions_applied: usize,
+    /// Number of revisions skipped due to low score
+    pub revisions_skipped: usize,
+    /// Number of applied revisions that removed whitespace (`allow_shrink`)
+    pub revisions_shrunk: usize,
+    /// Revisions applied in each iteration that made changes, in order
+    pub iterations: Vec<usize>,
+    /// True if the loop stopped because no revision passed `min_score`
+    /// (as opposed to hitting `max_iters` or finding no borders at all)
+    pub converged: bool,
+    /// Most common vertical border character in the corrected block
+    pub border_char: Option<char>,
+    /// Column of the rightmost right border in the corrected block
+    pub target_column: Option<usize>,
+    /// Every revision that was scored, applied or not, in order
+    pub revisions: Vec<RevisionRecord>,
+}
+
+/// A scored revision, as recorded in [`BlockCorrectionResult::revisions`]
+#[derive(Debug, Clone)]
+pub struct RevisionRecord {
+    /// The revision
+    pub revision: Revision,
+    /// Its score (0.0-1.0)
+    pub score: f64,
+    /// The terms that add up to `score`; see [`Revision::score_terms`]
+    pub terms: Vec<ScoreTerm>,
+    /// True if applied; false if it scored below `min_score`
+    pub applied: bool,
+    /// Change in the line's visual width had it been applied (negative
+    /// when whitespace is removed)
+    pub columns_added: isize,
+    /// Correction iteration it was scored in (0-based)
+    pub iteration: usize,
+}
+
+/// Correct a single diagram block using iterative refinement.
+///
+/// This is the core correction algorithm. It runs a loop that:
+/// 1. Analyzes all lines in the block to find their border positions
+/// 2. Re-aligns drifted left borders to their box's top-left corner; while
+///    any such revision applies, the right-border steps wait for the next pass
+/// 3. Determines the target column (rightmost border position); blocks of
+///    side-by-side boxes are split into column regions (see
+///    [`find_box_regions`]), each with its own target, and aligned one
+///    region at a time from left to right
+/// 4. Generates candidate revisions to align other lines to the target
+/// 5. Scores each revision and filters by `min_score`
+/// 6. Applies valid revisions
+/// 7. Repeats until no more revisions needed or `max_iters` reached
+///
+/// # Arguments
+///
+/// * `lines` - Mutable slice of all input lines (block is modified in place)
+/// * `block` - The block to correct (defines which lines to process)
+/// * `options` - Thresholds and iteration limits
+///
+/// # Returns
+///
+/// A `BlockCorrectionResult` with counts of applied and skipped revisions.
+pub fn correct_block(
+    lines: &mut [String],
+    block: &DiagramBlock,
+    options: &CorrectionOptions,
+) -> BlockCorrectionResult {
+    let ambiguous = options.ambiguous_width;
+    let mut result = BlockCorrectionResult::default();
+
+    for _ in 0..options.max_iters {
+        // Analyze current state
+        let block_lines: Vec<_> = lines[block.start..block.end].iter().collect();
+        let analyzed: Vec<_> = block_lines
+            .iter()
+            .map(|l| analyze_line_with(l, ambiguous))
+            .collect();
+
+        // Left edges first: re-indenting a row moves its right border too,
+        // so the right-border target is only meaningful once they agree.
+        let mut valid_revisions = score_and_filter(
+            prefix_revisions(&analyzed, block.start),
+            &analyzed,
+            block,
+            options,
+            &mut result,
+        );
+
+        if valid_revisions.is_empty() {
+            let block_strs: Vec<&str> = block_lines.iter().map(|s| s.as_str()).collect();
+            let border_char = detect_vertical_border(&block_strs);
+            let regions = find_box_regions_with(&block_strs, ambiguous);
+
+            // Side-by-side boxes are aligned one region at a time, left to
+            // right: padding a box's right border pushes every box after it
+            let candidates = if regions.len() < 2 {
+                let Some(revisions) =
+                    suffix_revisions(&analyzed, block.start, border_char, options.allow_shrink)
+                else {
+                    // No borders found, nothing to align
+                    break;
+                };
+                vec![revisions]
+            } else {
+                region_revisions(&analyzed, block.start, &regions, border_char, ambiguous)
+            };
+
+            for revisions in candidates {
+                valid_revisions =
+                    score_and_filter(revisions, &analyzed, block, options, &mut result);
+                if !valid_revisions.is_empty() {
+                    break;
+                }
+            }
+        }
+
+        if valid_revisions.is_empty() {
+            result.converged = true;
+            break;
+        }
+
+        // Apply revisions
+        for rev in &valid_revisions {
+            rev.apply_with(lines, ambiguous);
+        }
+
+        result.revisions_applied += valid_revisions.len();
+        result.revisions_shrunk += valid_revisions
+            .iter()
+            .filter(|r| matches!(r, Revision::RemoveSpacesBeforeSuffixBorder { .. }))
+            .count();
+        result.iterations.push(valid_revisions.len());
+    }
+
+    let block_lines: Vec<&str> = lines[block.start..block.end]
+        .iter()
+        .map(|s| s.as_str())
+        .collect();
+    result.target_column = block_lines
+        .iter()
+        .filter_map(|line| detect_suffix_border_with(line, ambiguous))
+        .map(|border| border.column)
+        .max();
+    if result.target_column.is_some() {
+        result.border_char = Some(detect_vertical_border(&block_lines));
+    }
+
+    result
+}
+
+/// Generate revisions that align every right border in the block to the
+/// rightmost one (or, with `allow_shrink`, to [`shrink_target`]), or `None`
+/// if no line has a right border
+fn suffix_revisions(
+    analyzed: &[AnalyzedLine],
+    block_start: usize,
+    border_char: char,
+    allow_shrink: bool,
+) -> Option<Vec<Revision>> {
+    let target = if allow_shrink {
+        shrink_target(analyzed)?
+    } else {
+        // Find target column (rightmost border position)
+        analyzed
+            .iter()
+            .filter_map(|a| a.suffix_border.as_ref().map(|b| b.column))
+            .max()?
+    };
+
+    let mut revisions = Vec::new();
+    for (i, analyzed_line) in analyzed.iter().enumerate() {
+        let global_idx = block_start + i;
+
+        if let Some(ref border) = analyzed_line.suffix_border {
+            if border.column < target {
+                revisions.push(suffix_revision(
+                    analyzed_line,
+                    global_idx,
+                    border.column,
+                    target,
+                ));
+            } else if border.column > target {
+                revisions.push(Revision::RemoveSpacesBeforeSuffixBorder {
+                    line_idx: global_idx,
+                    spaces_to_remove: border.column - target,
+                    target_column: target,
+                });
+            }
+        } else if analyzed_line.kind.is_boxy() {
+            // Consider adding a border
+            revisions.push(Revision::AddSuffixBorder {
+                line_idx: global_idx,
+                border_char,
+                target_column: target,
+            });
+        }
+    }
+    Some(revisions)
+}
+
+/// Target column for `allow_shrink`: where the box's closing corners sit
+/// (or, with no corners, where most right borders sit), raised to the
+/// narrowest column every line can reach by removing surplus spaces.
+/// Ties between columns go to the rightmost.
+fn shrink_target(analyzed: &[AnalyzedLine]) -> Option<usize> {
+    let borders: Vec<_> = analyzed
+        .iter()
+        .filter_map(|a| a.suffix_border.as_ref().map(|b| (a, b)))
+        .collect();
+
+    let mut counts = std::collections::BTreeMap::new();
+    let has_corners = borders.iter().any(|(_, b)| is_corner(b.char));
+    for (_, border) in &borders {
+        if !has_corners || is_corner(border.char) {
+            *counts.entry(border.column).or_insert(0) += 1;
+        }
+    }
+    let anchor = counts
+        .into_iter()
+        .max_by_key(|&(column, count)| (count, column))
+        .map(|(column, _)| column)?;
+
+    let floor = borders
+        .iter()
+        .map(|(a, b)| b.column - removable_spaces(&a.content))
+        .max()?;
+
+    Some(anchor.max(floor))
+}
+
+/// Number of spaces that can be removed before a line's suffix border:
+/// all spaces between the content and the border but one. Lines with
+/// nothing before the border (connectors) have none.
+fn removable_spaces(line: &str) -> usize {
+    let trimmed = line.trim_end();
+    let Some(last_char) = trimmed.chars().next_back() else {
+        return 0;
+    };
+    let prefix = &trimmed[..trimmed.len() - last_char.len_utf8()];
+    let content = prefix.trim_end_matches(' ');
+    if content.trim_start().is_empty() {
+        return 0;
+    }
+    (prefix.len() - content.len()).saturating_sub(1)
+}
+
+/// Generate revisions for a block of side-by-side boxes, one list per
+/// region, each aligning that box's right borders to its rightmost one
+fn region_revisions(
+    analyzed: &[AnalyzedLine],
+    block_start: usize,
+    regions: &[BoxRegion],
+    border_char: char,
+    ambiguous: AmbiguousWidth,
+) -> Vec<Vec<Revision>> {
+    let slices: Vec<Vec<BoxSlice>> = analyzed
+        .iter()
+        .map(|a| {
+            if a.kind.is_boxy() {
+                box_slices(&a.content, regions, ambiguous)
+            } else {
+                Vec::new()
+            }
+        })
+        .collect();
+
+    let mut targets = vec![None; regions.len()];
+    for slice in slices.iter().flatten() {
+        if let Some(right) = slice.right() {
+            targets[slice.region] = targets[slice.region].max(Some(right.column));
+        }
+    }
+
+    let mut revisions = vec![Vec::new(); regions.len()];
+    for (i, (analyzed_line, line_slices)) in analyzed.iter().zip(&slices).enumerate() {
+        let global_idx = block_start + i;
+
+        for (n, slice) in line_slices.iter().enumerate() {
+            let Some(target) = targets[slice.region] else {
+                continue;
+            };
+
+            match slice.right() {
+                Some(right) if right.column < target => {
+                    let is_suffix = analyzed_line
+                        .suffix_border
+                        .as_ref()
+                        .is_some_and(|b| b.column == right.column);
+                    let fill = (analyzed_line.kind == LineKind::Strong)
+                        .then_some(right.fill)
+                        .flatten();
+                    revisions[slice.region].push(if is_suffix {
+                        suffix_revision(analyzed_line, global_idx, right.column, target)
+                    } else {
+                        Revision::PadBeforeInteriorBorder {
+                            line_idx: global_idx,
+                            border_column: right.column,
+                            fill_char: fill.unwrap_or(' '),
+                            chars_to_add: target - right.column,
+                            target_column: target,
+                        }
+                    });
+                }
+                None if n + 1 == line_slices.len() && analyzed_line.suffix_border.is_none() => {
+                    revisions[slice.region].push(Revision::AddSuffixBorder {
+                        line_idx: global_idx,
+                        border_char,
+                        target_column: target,
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+    revisions
+}
+
+/// Pad a line's suffix border out to the target column, extending it with
+/// its fill character if the line is a horizontal rule
+fn suffix_revision(
+    analyzed_line: &AnalyzedLine,
+    line_idx: usize,
+    column: usize,
+    target: usize,
+) -> Revision {
+    let missing = target - column;
+    let rule_fill = (analyzed_line.kind == LineKind::Strong)
+        .then(|| detect_rule_fill(&analyzed_line.content))
+        .flatten();
+    match rule_fill {
+        Some(fill_char) => Revision::ExtendHorizontalRule {
+            line_idx,
+            fill_char,
+            chars_to_add: missing,
+            target_column: target,
+        },
+        None => Revision::PadBeforeSuffixBorder {
+            line_idx,
+            spaces_to_add: missing,
+            target_column: target,
+        },
+    }
+}
+
+/// A border character on a line
+#[derive(Debug, Clone, Copy)]
+struct LineBorder {
+    /// Visual column of the border
+    column: usize,
+    /// The horizontal fill just before it, if the border ends a rule
+    fill: Option<char>,
+}
+
+/// The part of a line that belongs to one box region
+#[derive(Debug)]
+struct BoxSlice {
+    /// Index into the block's regions
+    region: usize,
+    /// Visual column of the slice's left border
+    left: usize,
+    /// The rightmost border in the slice
+    last: LineBorder,
+}
+
+impl BoxSlice {
+    /// The slice's right border, if it has one besides its left border
+    fn right(&self) -> Option<LineBorder> {
+        (self.last.column > self.left).then_some(self.last)
+    }
+}
+
+/// Split a line into per-region slices.
+///
+/// Border characters are paired left to right into `| ... |` spans, and a
+/// span is assigned to the region it overlaps most, so drifted rows that
+/// sit partly outside their box's columns still find it. A pair whose
+/// closing border lies inside a different region (e.g. two vertical
+/// connectors under neighbouring boxes) is not a box row and is split.
+/// Consecutive spans in the same region (interior dividers, nested boxes)
+/// are merged.
+fn box_slices(line: &str, regions: &[BoxRegion], ambiguous: AmbiguousWidth) -> Vec<BoxSlice> {
+    let mut borders = Vec::new();
+    let mut column = 0;
+    let mut prev = None;
+    for (_, c, width) in clusters(line, ambiguous) {
+        if is_border_char(c) {
+            borders.push(LineBorder {
+                column,
+                fill: prev.filter(|&p| is_horizontal_fill(p)),
+            });
+        }
+        prev = Some(c);
+        column += width;
+    }
+
+    let containing = |column: usize| {
+        regions
+            .iter()
+            .position(|r| (r.left..=r.right).contains(&column))
+    };
+
+    let mut slices: Vec<BoxSlice> = Vec::new();
+    let mut i = 0;
+    while i < borders.len() {
+        let left = borders[i];
+        let paired = borders.get(i + 1).and_then(|&right| {
+            let region = region_of_span(left.column, right.column, regions);
+            containing(right.column)
+                .is_none_or(|r| r == region)
+                .then_some((region, right))
+        });
+        let (region, last) =
+            paired.unwrap_or_else(|| (region_of_span(left.column, left.column, regions), left));
+        i += if paired.is_some() { 2 } else { 1 };
+
+        match slices.last_mut() {
+            Some(slice) if slice.region == region => slice.last = last,
+            _ => slices.push(BoxSlice {
+                region,
+                left: left.column,
+                last,
+            }),
+        }
+    }
+    slices
+}
+
+/// The region a span of columns overlaps most, or else the one whose left
+/// edge is nearest to the span's start
+fn region_of_span(left: usize, right: usize, regions: &[BoxRegion]) -> usize {
+    let overlap = |r: &BoxRegion| (right.min(r.right) + 1).saturating_sub(left.max(r.left));
+    regions
+        .iter()
+        .enumerate()
+        .filter(|(_, r)| overlap(r) > 0)
+        .max_by_key(|(_, r)| overlap(r))
+        .or_else(|| {
+            regions
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, r)| r.left.abs_diff(left))
+        })
+        .map_or(0, |(i, _)| i)
+}
+
+/// Keep the revisions that meet `min_score`, counting the rest as skipped
+fn score_and_filter(
+    revisions: Vec<Revision>,
+    analyzed: &[AnalyzedLine],
+    block: &DiagramBlock,
+    options: &CorrectionOptions,
+    result: &mut BlockCorrectionResult,
+) -> Vec<Revision> {
+    let iteration = result.iterations.len();
+    let mut valid = Vec::new();
+    for revision in revisions {
+        let score = revision.score(analyzed, block.start);
+        let applied = score >= options.min_score;
+        result.revisions.push(RevisionRecord {
+            columns_added: columns_added(&revision, analyzed, block.start, options.ambiguous_width),
+            terms: revision.score_terms(analyzed, block.start),
+            revision: revision.clone(),
+            score,
+            applied,
+            iteration,
+        });
+        if applied {
+            valid.push(revision);
+        } else {
+            result.revisions_skipped += 1;
+        }
+    }
+    valid
+}
+
+/// How much a revision would change its line's visual width
+fn columns_added(
+    revision: &Revision,
+    analyzed: &[AnalyzedLine],
+    block_start: usize,
+    ambiguous: AmbiguousWidth,
+) -> isize {
+    match revision {
+        Revision::PadBeforeSuffixBorder { spaces_to_add, .. } => *spaces_to_add as isize,
+        Revision::ExtendHorizontalRule {
+            fill_char,
+            chars_to_add,
+            ..
+        }
+        | Revision::PadBeforeInteriorBorder {
+            fill_char,
+            chars_to_add,
+            ..
+        } => (chars_to_add * char_width_with(*fill_char, ambiguous)) as isize,
+        Revision::RemoveSpacesBeforeSuffixBorder {
+            spaces_to_remove, ..
+        } => -(*spaces_to_remove as isize),
+        Revision::AddSuffixBorder {
+            line_idx,
+            target_column,
+            ..
+        } => {
+            let line = &analyzed[line_idx - block_start].content;
+            let content_width = visual_width_with(line.trim_end(), ambiguous);
+            let new_width = (*target_column).max(content_width) + 1;
+            new_width as isize - visual_width_with(line, ambiguous) as isize
+        }
+        Revision::AlignPrefixBorder {
+            current_column,
+            target_column,
+            ..
+        } => *target_column as isize - *current_column as isize,
+    }
+}
+
+/// Work out, for each line of a block, which left-border column it should
+/// have: the column of the top-left corner of the box it belongs to.
+///
+/// A box opens at a line whose left border is a corner. The rows that
+/// follow (left border `|`, `│`, `├`, ...) belong to it, and the next
+/// corner-led line closes it (or separates it, if more rows follow).
+/// Any line without a left border ends the box, so connectors and prose
+/// between boxes are never re-indented. Lines that are a single border
+/// character (e.g. a vertical connector `|`) are not treated as rows, and
+/// neither is a line of bare borders right after a corner-led line unless
+/// it spans the same width (so `|    |` connectors under side-by-side
+/// boxes stay put).
+fn box_left_edges(analyzed: &[AnalyzedLine]) -> Vec<Option<usize>> {
+    // (left column of the open box, width of the previous line if it was
+    // corner-led)
+    let mut open: Option<(usize, Option<Option<usize>>)> = None;
+
+    analyzed
+        .iter()
+        .map(|line| {
+            let Some(border) = &line.prefix_border else {
+                open = None;
+                return None;
+            };
+            let width = line
+                .suffix_border
+                .as_ref()
+                .map(|s| s.column.saturating_sub(border.column));
+
+            if border.is_corner {
+                match open {
+                    // Bottom edge or separator of the open box
+                    Some((left, None)) => {
+                        open = Some((left, Some(width)));
+                        Some(left)
+                    }
+                    // Top edge of a new box
+                    _ => {
+                        open = Some((border.column, Some(width)));
+                        None
+                    }
+                }
+            } else {
+                let bare = line
+                    .content
+                    .chars()
+                    .all(|c| c.is_whitespace() || is_border_char(c));
+                let is_row = line.content.len() > line.indent + border.char.len_utf8();
+                match open {
+                    Some((left, edge_width))
+                        if is_row && !(bare && edge_width.is_some_and(|w| w != width)) =>
+                    {
+                        open = Some((left, None));
+                        Some(left)
+                    }
+                    _ => {
+                        open = None;
+                        None
+                    }
+                }
+            }
+        })
+        .collect()
+}
+
+/// Generate revisions that move drifted left borders back under their
+/// box's top-left corner
+fn prefix_revisions(analyzed: &[AnalyzedLine], block_start: usize) -> Vec<Revision> {
+    box_left_edges(analyzed)
+        .into_iter()
+        .zip(analyzed)
+        .enumerate()
+        .filter_map(|(i, (target, line))| {
+            let target_column = target?;
+            let current_column = line.prefix_border.as_ref()?.column;
+            (current_column != target_column).then_some(Revision::AlignPrefixBorder {
+                line_idx: block_start + i,
+                current_column,
+                target_column,
+            })
+        })
+        .collect()
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Main Correction Logic
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Expand tabs to spaces, accounting for character visual width.
+///
+/// Tab stops are calculated based on visual columns, not character count.
+/// This ensures correct alignment when CJK or other wide characters are present.
+pub fn expand_tabs(line: &str, tab_width: usize) -> String {
+    expand_tabs_with(line, tab_width, AmbiguousWidth::Narrow)
+}
+
+/// [`expand_tabs`] with ambiguous characters measured as `ambiguous`
+pub fn expand_tabs_with(line: &str, tab_width: usize, ambiguous: AmbiguousWidth) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut col = 0;
+
+    for grapheme in line.graphemes(true) {
+        if grapheme == "\t" {
+            let spaces = tab_width - (col % tab_width);
+            result.extend(std::iter::repeat_n(' ', spaces));
+            col += spaces;
+        } else {
+            result.push_str(grapheme);
+            col += grapheme_width(grapheme, ambiguous);
+        }
+    }
+
+    result
+}
+
+/// Check if a block overlaps with any of the given line ranges
+/// Block indices are 0-indexed, ranges are 1-indexed
+pub fn block_overlaps_ranges(block: &DiagramBlock, ranges: &[LineRange]) -> bool {
+    // Convert block to 1-indexed for comparison with ranges
+    let block_start = block.start + 1;
+    let block_end = block.end; // end is already exclusive, so it's effectively 1-indexed
+
+    ranges.iter().any(|r| {
+        // Check if block and range overlap
+        block_start <= r.end && block_end >= r.start
+    })
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Markdown Fences
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Find the content lines of fenced code blocks in a Markdown document.
+///
+/// Follows CommonMark: a fence is three or more backticks or tildes,
+/// indented by at most three spaces, optionally followed by an info string.
+/// It is closed by a line of at least as many of the same character and
+/// nothing else; an unclosed fence runs to the end of the document.
+///
+/// With `info_filter`, only fences whose language (the first word of the info
+/// string) equals one of the given words, compared case-insensitively, are
+/// returned: `text title="x"` matches `text`, but `textile` does not. Returned
+/// ranges exclude the fence lines themselves.
+pub fn find_fenced_blocks(lines: &[String], info_filter: Option<&[String]>) -> Vec<Range<usize>> {
+    let mut fences = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let Some((fence_char, fence_len, info)) = parse_fence(&lines[i]) else {
+            i += 1;
+            continue;
+        };
+
+        let start = i + 1;
+        let end = lines[start..]
+            .iter()
+            .position(|l| {
+                parse_fence(l).is_some_and(|(c, len, info)| {
+                    c == fence_char && len >= fence_len && info.is_empty()
+                })
+            })
+            .map_or(lines.len(), |offset| start + offset);
+
+        let language = info.split_whitespace().next().unwrap_or("");
+        let wanted = info_filter
+            .is_none_or(|filter| filter.iter().any(|w| w.eq_ignore_ascii_case(language)));
+        if wanted {
+            fences.push(start..end);
+        }
+
+        // Skip past the closing fence
+        i = end + 1;
+    }
+
+    fences
+}
+
+/// Parse a fence line into its character, length and info string
+fn parse_fence(line: &str) -> Option<(char, usize, &str)> {
+    let rest = line.trim_start_matches(' ');
+    if line.len() - rest.len() > 3 {
+        return None;
+    }
+
+    let fence_char = rest.chars().next().filter(|&c| c == '`' || c == '~')?;
+    let info = rest.trim_start_matches(fence_char);
+    let fence_len = rest.len() - info.len();
+    let info = info.trim();
+
+    // Backtick fences can't have backticks in their info string (that's
+    // inline code)
+    if fence_len < 3 || (fence_char == '`' && info.contains('`')) {
+        return None;
+    }
+    Some((fence_char, fence_len, info))
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Source Comments
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Line comment leaders, longest first. `*` covers the continuation lines
+/// of `/* ... */` blocks; `--` is left out since `-` is a box fill
+/// character.
+const COMMENT_LEADERS: &[&str] = &["///", "//!", "//", "#", "*"];
+
+/// A run of consecutive comment lines sharing one comment prefix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommentRun {
+    /// Line range of the run
+    pub lines: Range<usize>,
+    /// Prefix stripped from each line: indentation, the comment leader and
+    /// the single space after it when every non-empty line has one
+    pub prefix: String,
+}
+
+impl CommentRun {
+    /// The text of a line in this run with the comment prefix removed.
+    ///
+    /// Lines that are only the leader yield an empty payload. Directive
+    /// lines (`// aadc: off`) are kept whole so they are still recognized.
+    pub fn payload(&self, line: &str) -> String {
+        if !parse_directives(line).is_empty() {
+            return line.trim().to_string();
+        }
+        line.strip_prefix(self.prefix.as_str())
+            .unwrap_or("")
+            .to_string()
+    }
+}
+
+/// Split a line into its comment head (indentation and leader) and the
+/// rest, or `None` if it is not a line comment
+fn split_comment(line: &str) -> Option<(&str, &str)> {
+    let indent = line.len() - line.trim_start().len();
+    let leader = COMMENT_LEADERS
+        .iter()
+        .find(|leader| line[indent..].starts_with(**leader))?;
+    Some(line.split_at(indent + leader.len()))
+}
+
+/// Find runs of line comments in source code.
+///
+/// A run is a maximal sequence of consecutive lines with the same
+/// indentation and comment leader. Its prefix includes one space after the
+/// leader if every line with text after the leader starts with one, so
+/// `// +--+` and `//+--+` styles both strip to `+--+`.
+pub fn find_comment_runs(lines: &[String]) -> Vec<CommentRun> {
+    let mut runs = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let Some((head, _)) = split_comment(&lines[i]) else {
+            i += 1;
+            continue;
+        };
+
+        let start = i;
+        let mut spaced = true;
+        while let Some((line_head, rest)) = lines.get(i).and_then(|line| split_comment(line)) {
+            if line_head != head {
+                break;
+            }
+            spaced &= rest.is_empty() || rest.starts_with(' ');
+            i += 1;
+        }
+
+        let prefix = if spaced {
+            format!("{head} ")
+        } else {
+            head.to_string()
+        };
+        runs.push(CommentRun {
+            lines: start..i,
+            prefix,
+        });
+    }
+
+    runs
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Corrector (Public Entry Point)
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// What happened to a single detected block.
+#[derive(Debug, Clone)]
+pub struct BlockReport {
+    /// The detected block (line indices refer to the corrected output)
+    pub block: DiagramBlock,
+    /// Correction outcome, or `None` if the block was skipped because it
+    /// does not overlap [`CorrectionOptions::lines`]
+    pub result: Option<BlockCorrectionResult>,
+}
+
+/// Structured description of a correction run.
+#[derive(Debug, Clone, Default)]
+pub struct CorrectionReport {
+    /// Aggregate counters and timing
+    pub stats: Stats,
+    /// Quick-scan outcome, if the quick scan ran (it is bypassed by
+    /// [`CorrectionOptions::all_blocks`] and by an `aadc: force` directive)
+    pub quick_scan: Option<QuickScanResult>,
+    /// Per-block outcomes, in input order
+    pub blocks: Vec<BlockReport>,
+    /// Line ranges of the fenced code blocks that were searched, in
+    /// Markdown mode (`None` otherwise)
+    pub fences: Option<Vec<Range<usize>>>,
+    /// Comment runs that were searched, in comment mode (`None` otherwise)
+    pub comments: Option<Vec<CommentRun>>,
+}
+
+impl CorrectionReport {
+    /// True if the quick scan found no candidate diagram region and the
+    /// input was returned untouched without running block detection.
+    pub fn passed_through(&self) -> bool {
+        self.quick_scan
+            .as_ref()
+            .is_some_and(|scan| !scan.likely_has_diagrams)
+    }
+}
+
+/// Result of [`Corrector::correct_str`].
+#[derive(Debug, Clone)]
+pub struct Correction {
+    /// The corrected text
+    pub output: String,
+    /// True if `output` differs from the input
+    pub changed: bool,
+    /// What was detected and changed
+    pub report: CorrectionReport,
+}
+
+/// Aligns the diagrams in a piece of text.
+///
+/// A `Corrector` is cheap to construct and holds no per-input state, so a
+/// single instance can be reused across many documents.
+#[derive(Debug, Clone, Default)]
+pub struct Corrector {
+    options: CorrectionOptions,
+}
+
+impl Corrector {
+    /// Create a corrector with the given options
+    pub fn new(options: CorrectionOptions) -> Self {
+        Self { options }
+    }
+
+    /// The options this corrector was built with
+    pub fn options(&self) -> &CorrectionOptions {
+        &self.options
+    }
+
+    /// Correct a string, preserving each line's ending (`\n` or `\r\n`)
+    /// and whether it ended with a newline.
+    pub fn correct_str(&self, input: &str) -> Correction {
+        let (lines, endings): (Vec<String>, Vec<&str>) = input
+            .split_inclusive('\n')
+            .map(|line| {
+                let content = line
+                    .strip_suffix("\r\n")
+                    .or_else(|| line.strip_suffix('\n'))
+                    .unwrap_or(line);
+                (content.to_string(), &line[content.len()..])
+            })
+            .unzip();
+        let (corrected, report) = self.correct_lines(lines);
+
+        let mut output = String::with_capacity(input.len());
+        for (line, ending) in corrected.iter().zip(endings) {
+            output.push_str(line);
+            output.push_str(ending);
+        }
+        let changed = output != input;
+
+        Correction {
+            output,
+            changed,
+            report,
+        }
+    }
+
+    /// Correct a document given as lines (without line terminators).
+    pub fn correct_lines(&self, lines: Vec<String>) -> (Vec<String>, CorrectionReport) {
+        self.run_with(lines, &mut Directives::default())
+    }
+
+    /// Detect and correct every block in a document, continuing from the
+    /// directive state left by earlier input
+    fn run_with(
+        &self,
+        lines: Vec<String>,
+        directives: &mut Directives,
+    ) -> (Vec<String>, CorrectionReport) {
+        let start_time = Instant::now();
+        let options = &self.options;
+        let mut report = CorrectionReport::default();
+        report.stats.total_lines = lines.len();
+
+        // In Markdown mode only fenced code blocks are searched; prose,
+        // tables and rules between them pass through byte-for-byte
+        // In comment mode only runs of line comments are searched, and the
+        // code around them likewise passes through
+        let regions: Vec<Range<usize>> = if options.markdown {
+            let fences = find_fenced_blocks(&lines, options.fence_info.as_deref());
+            report.fences = Some(fences.clone());
+            fences
+        } else if options.comments {
+            let runs = find_comment_runs(&lines);
+            let regions = runs.iter().map(|run| run.lines.clone()).collect();
+            report.comments = Some(runs);
+            regions
+        } else {
+            std::iter::once(0..lines.len()).collect()
+        };
+
+        // An `aadc: force` directive must be honored even in a document
+        // the quick scan would pass through
+        let forced = directives.force_next
+            || lines
+                .iter()
+                .any(|line| parse_directives(line).contains(&Directive::Force));
+        if !options.all_blocks && !forced {
+            let scan = quick_scan_regions(&lines, &regions);
+            let likely_has_diagrams = scan.likely_has_diagrams;
+            report.quick_scan = Some(scan);
+            if !likely_has_diagrams {
+                // Directives still carry over to whatever input follows
+                for line in &lines {
+                    directives.apply_line(line);
+                }
+                report.stats.elapsed = start_time.elapsed();
+                return (lines, report);
+            }
+        }
+
+        let mut lines = lines;
+        let mut scanned = 0;
+        let runs = report.comments.clone().unwrap_or_default();
+        for (i, region) in regions.into_iter().enumerate() {
+            // Directives between fences (Markdown comments) still count
+            for line in &lines[scanned..region.start] {
+                directives.apply_line(line);
+            }
+            scanned = region.end;
+            match runs.get(i) {
+                Some(run) => self.correct_comment_run(&mut lines, run, &mut report, directives),
+                None => self.correct_region(&mut lines, region, &mut report, directives),
+            }
+        }
+        for line in &lines[scanned..] {
+            directives.apply_line(line);
+        }
+
+        report.stats.elapsed = start_time.elapsed();
+        (lines, report)
+    }
+
+    /// Correct the payload of a comment run and put the prefix back.
+    ///
+    /// Lines the correction left alone are restored byte-for-byte; block
+    /// and revision columns are shifted to refer to the full line.
+    fn correct_comment_run(
+        &self,
+        lines: &mut [String],
+        run: &CommentRun,
+        report: &mut CorrectionReport,
+        directives: &mut Directives,
+    ) {
+        let originals: Vec<String> = lines[run.lines.clone()]
+            .iter_mut()
+            .map(|line| {
+                let payload = run.payload(line);
+                std::mem::replace(line, payload)
+            })
+            .collect();
+
+        let first_block = report.blocks.len();
+        self.correct_region(lines, run.lines.clone(), report, directives);
+
+        for (line, original) in lines[run.lines.clone()].iter_mut().zip(originals) {
+            *line = if *line == run.payload(&original) {
+                original
+            } else {
+                format!("{}{}", run.prefix, line)
+            };
+        }
+
+        let ambiguous = self.options.ambiguous_width;
+        let shift = visual_width_with(
+            &expand_tabs_with(&run.prefix, self.options.tab_width, ambiguous),
+            ambiguous,
+        );
+        for result in report.blocks[first_block..]
+            .iter_mut()
+            .filter_map(|block| block.result.as_mut())
+        {
+            result.target_column = result.target_column.map(|column| column + shift);
+            for record in &mut result.revisions {
+                record.revision.shift_columns(shift);
+            }
+        }
+    }
+
+    /// Expand tabs in, find blocks in, and correct one region of the input
+    fn correct_region(
+        &self,
+        lines: &mut [String],
+        region: Range<usize>,
+        report: &mut CorrectionReport,
+        directives: &mut Directives,
+    ) {
+        let options = &self.options;
+
+        // Detect on a tab-expanded view; lines are only written back
+        // expanded where the tab mode asks for it
+        let expanded: Vec<String> = lines[region.clone()]
+            .iter()
+            .map(|line| expand_tabs_with(line, options.tab_width, options.ambiguous_width))
+            .collect();
+        if options.tabs == TabExpansion::ExpandAll {
+            lines[region.clone()].clone_from_slice(&expanded);
+        }
+
+        // Find diagram blocks
+        let blocks = find_diagram_blocks_with(&expanded, options.all_blocks, directives);
+        report.stats.blocks_found += blocks.len();
+
+        // Correct each block
+        for mut block in blocks {
+            block.start += region.start;
+            block.end += region.start;
+
+            // Check if block overlaps with line ranges (if specified)
+            if let Some(ref ranges) = options.lines {
+                if !block_overlaps_ranges(&block, ranges) {
+                    report.stats.blocks_skipped += 1;
+                    report.blocks.push(BlockReport {
+                        block,
+                        result: None,
+                    });
+                    continue;
+                }
+            }
+
+            let local = block.start - region.start..block.end - region.start;
+            let original: Vec<String> = lines[block.start..block.end].to_vec();
+            lines[block.start..block.end].clone_from_slice(&expanded[local.clone()]);
+
+            let result = match block.min_score {
+                Some(min_score) => {
+                    let options = CorrectionOptions {
+                        min_score,
+                        ..options.clone()
+                    };
+                    correct_block(lines, &block, &options)
+                }
+                None => correct_block(lines, &block, options),
+            };
+
+            match options.tabs {
+                TabExpansion::Preserve => {
+                    let block_lines = &mut lines[block.start..block.end];
+                    for ((line, before), after) in
+                        block_lines.iter_mut().zip(original).zip(&expanded[local])
+                    {
+                        if line == after {
+                            *line = before;
+                        }
+                    }
+                }
+                // A block that needed no revision is left as it was
+                TabExpansion::ExpandBlocks if result.revisions_applied == 0 => {
+                    lines[block.start..block.end].clone_from_slice(&original);
+                }
+                _ => {}
+            }
+
+            if result.revisions_applied > 0 {
+                report.stats.blocks_modified += 1;
+                report.stats.total_revisions += result.revisions_applied;
+                report.stats.revisions_shrunk += result.revisions_shrunk;
+            }
+            report.stats.revisions_skipped += result.revisions_skipped;
+            report.blocks.push(BlockReport {
+                block,
+                result: Some(result),
+            });
+        }
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Streaming
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Consecutive lines that are neither box lines nor part of one that end
+/// every diagram block: block detection never bridges more than one blank
+/// line or two plain lines
+const STREAM_SEPARATOR_LINES: usize = 3;
+
+/// Lines buffered before a segment is corrected and written, when a
+/// separator allows it
+const STREAM_BATCH_LINES: usize = 1024;
+
+/// Most lines [`Corrector::correct_stream`] holds at once. A diagram longer
+/// than this is corrected in pieces.
+pub const STREAM_MAX_BUFFERED_LINES: usize = 100_000;
+
+/// Longest line, in bytes including its terminator, that
+/// [`Corrector::correct_stream`] reads as a whole. Longer lines are never
+/// part of a diagram; they are passed through in chunks of this size.
+pub const STREAM_MAX_LINE_BYTES: usize = 64 * 1024;
+
+/// Result of [`Corrector::correct_stream`].
+#[derive(Debug, Clone)]
+pub struct StreamCorrection {
+    /// True if the output differs from the input
+    pub changed: bool,
+    /// What was detected and changed; line indices refer to the whole
+    /// stream
+    pub report: CorrectionReport,
+}
+
+impl Corrector {
+    /// Correct text read from `reader`, writing it to `writer` as it goes.
+    ///
+    /// Memory stays bounded regardless of input size: lines are buffered
+    /// only until a run of lines that no diagram block can span, then that
+    /// segment is corrected and written out. Directives carry over between
+    /// segments. Line endings (`\n` or `\r\n`, per line), a leading byte
+    /// order mark and a missing final newline are preserved.
+    ///
+    /// A line longer than [`STREAM_MAX_LINE_BYTES`] is written out unchanged
+    /// as it is read, and ends any diagram block before it.
+    ///
+    /// The input is always treated as plain text: [`CorrectionOptions::markdown`]
+    /// and [`CorrectionOptions::comments`] are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Read and write errors are passed through. Input that is not valid
+    /// UTF-8 or contains NUL bytes fails with [`std::io::ErrorKind::InvalidData`];
+    /// whatever was already written stays written.
+    pub fn correct_stream<R: std::io::BufRead, W: std::io::Write>(
+        &self,
+        mut reader: R,
+        mut writer: W,
+    ) -> std::io::Result<StreamCorrection> {
+        let start_time = Instant::now();
+        let mut stream = StreamState {
+            report: CorrectionReport {
+                quick_scan: Some(QuickScanResult::default()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        let mut quiet = 0;
+
+        loop {
+            buf.clear();
+            let limit = STREAM_MAX_LINE_BYTES as u64;
+            if (&mut reader).take(limit).read_until(b'\n', &mut buf)? == 0 {
+                break;
+            }
+            if buf.len() == STREAM_MAX_LINE_BYTES && !buf.ends_with(b"\n") {
+                // Too long to be a diagram row: write out what came before,
+                // then pass the line through without holding it whole
+                self.flush_segment(&mut stream, &mut writer)?;
+                stream.pass_long_line(&mut reader, &mut buf, &mut writer)?;
+                quiet = 0;
+                continue;
+            }
+            let line = stream.decode_line(&mut buf, &mut writer)?;
+
+            let boxy = parse_directives(&line).is_empty()
+                && classify_line(&expand_tabs_with(
+                    &line,
+                    self.options.tab_width,
+                    self.options.ambiguous_width,
+                ))
+                .is_boxy();
+            quiet = if boxy { 0 } else { quiet + 1 };
+            stream.lines.push(line);
+
+            let separated = quiet >= STREAM_SEPARATOR_LINES;
+            if (separated && stream.lines.len() >= STREAM_BATCH_LINES)
+                || stream.lines.len() >= STREAM_MAX_BUFFERED_LINES
+            {
+                self.flush_segment(&mut stream, &mut writer)?;
+            }
+        }
+        self.flush_segment(&mut stream, &mut writer)?;
+        writer.flush()?;
+
+        let mut report = stream.report;
+        report.stats.elapsed = start_time.elapsed();
+        Ok(StreamCorrection {
+            changed: stream.changed,
+            report,
+        })
+    }
+
+    /// Correct the buffered lines, write them out and fold their report
+    /// into the stream's
+    fn flush_segment<W: std::io::Write>(
+        &self,
+        stream: &mut StreamState,
+        writer: &mut W,
+    ) -> std::io::Result<()> {
+        if stream.lines.is_empty() {
+            return Ok(());
+        }
+        let offset = stream.offset;
+        let lines = std::mem::take(&mut stream.lines);
+
+        // Line ranges are 1-based and global; shift them into the segment
+        let options = CorrectionOptions {
+            markdown: false,
+            comments: false,
+            lines: self.options.lines.as_ref().map(|ranges| {
+                ranges
+                    .iter()
+                    .filter(|range| range.end > offset)
+                    .map(|range| LineRange {
+                        start: range.start.saturating_sub(offset).max(1),
+                        end: range.end.saturating_sub(offset),
+                    })
+                    .collect()
+            }),
+            ..self.options.clone()
+        };
+        let (corrected, segment) =
+            Corrector::new(options).run_with(lines.clone(), &mut stream.directives);
+        stream.changed |= corrected != lines;
+
+        for (line, ending) in corrected.iter().zip(stream.endings.drain(..)) {
+            writer.write_all(line.as_bytes())?;
+            writer.write_all(ending.as_bytes())?;
+        }
+
+        let report = &mut stream.report;
+        report.stats.merge(&segment.stats);
+        report
+            .blocks
+            .extend(segment.blocks.into_iter().map(|mut block| {
+                block.block.start += offset;
+                block.block.end += offset;
+                block
+            }));
+        // The stream counts as scanned only if every segment was
+        match (&mut report.quick_scan, segment.quick_scan) {
+            (Some(total), Some(scan)) => {
+                total.lines_scanned += scan.lines_scanned;
+                total.lines_with_box_chars += scan.lines_with_box_chars;
+                total.regions.extend(
+                    scan.regions
+                        .into_iter()
+                        .map(|region| region.start + offset..region.end + offset),
+                );
+                total.likely_has_diagrams |= scan.likely_has_diagrams;
+            }
+            _ => report.quick_scan = None,
+        }
+
+        stream.offset += corrected.len();
+        Ok(())
+    }
+}
+
+/// Buffered state of a [`Corrector::correct_stream`] run
+#[derive(Default)]
+struct StreamState {
+    /// Lines waiting to be corrected, without terminators
+    lines: Vec<String>,
+    /// Terminator of each buffered line (`""` for a final line without one)
+    endings: Vec<&'static str>,
+    /// Index of the first buffered line in the whole stream
+    offset: usize,
+    /// Bytes read so far, for error positions
+    bytes_read: usize,
+    directives: Directives,
+    report: CorrectionReport,
+    changed: bool,
+}
+
+impl StreamState {
+    /// Split a raw line into its text and terminator, writing a leading
+    /// byte order mark straight through
+    fn decode_line<W: std::io::Write>(
+        &mut self,
+        buf: &mut Vec<u8>,
+        writer: &mut W,
+    ) -> std::io::Result<String> {
+        let invalid =
+            |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+
+        if let Some(pos) = buf.iter().position(|&b| b == 0) {
+            return Err(invalid(format!(
+                "Input appears to be binary (NUL byte at position {})",
+                self.bytes_read + pos
+            )));
+        }
+
+        let read = buf.len();
+        let ending = if buf.ends_with(b"\r\n") {
+            "\r\n"
+        } else if buf.ends_with(b"\n") {
+            "\n"
+        } else {
+            ""
+        };
+        buf.truncate(buf.len() - ending.len());
+
+        let mut line = match std::str::from_utf8(buf) {
+            Ok(text) => text.to_string(),
+            Err(err) => {
+                let position = self.bytes_read + err.valid_up_to();
+                return Err(invalid(format!(
+                    "Invalid UTF-8 at byte position {} (byte value: 0x{:02X})",
+                    position,
+                    buf[err.valid_up_to()]
+                )));
+            }
+        };
+        if self.bytes_read == 0 {
+            if let Some(rest) = line.strip_prefix('\u{FEFF}') {
+                writer.write_all('\u{FEFF}'.to_string().as_bytes())?;
+                line = rest.to_string();
+            }
+        }
+
+        self.bytes_read += read;
+        self.endings.push(ending);
+        Ok(line)
+    }
+
+    /// Write a line longer than [`STREAM_MAX_LINE_BYTES`] straight through,
+    /// reading the rest of it a chunk at a time. `buf` holds the first
+    /// chunk; the line's text is checked as [`StreamState::decode_line`]
+    /// would.
+    fn pass_long_line<R: std::io::BufRead, W: std::io::Write>(
+        &mut self,
+        reader: &mut R,
+        buf: &mut Vec<u8>,
+        writer: &mut W,
+    ) -> std::io::Result<()> {
+        let invalid =
+            |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+
+        loop {
+            if let Some(pos) = buf.iter().position(|&b| b == 0) {
+                return Err(invalid(format!(
+                    "Input appears to be binary (NUL byte at position {})",
+                    self.bytes_read + pos
+                )));
+            }
+            // A chunk may end partway through a character; carry that over
+            let valid = match std::str::from_utf8(buf) {
+                Ok(_) => buf.len(),
+                Err(err) if err.error_len().is_none() => err.valid_up_to(),
+                Err(err) => {
+                    return Err(invalid(format!(
+                        "Invalid UTF-8 at byte position {} (byte value: 0x{:02X})",
+                        self.bytes_read + err.valid_up_to(),
+                        buf[err.valid_up_to()]
+                    )));
+                }
+            };
+            writer.write_all(&buf[..valid])?;
+            self.bytes_read += valid;
+            if buf.ends_with(b"\n") {
+                break;
+            }
+            buf.drain(..valid);
+
+            let carried = buf.len();
+            let limit = (STREAM_MAX_LINE_BYTES - carried) as u64;
+            if (&mut *reader).take(limit).read_until(b'\n', buf)? == 0 {
+                if carried > 0 {
+                    return Err(invalid(format!(
+                        "Invalid UTF-8 at byte position {} (byte value: 0x{:02X})",
+                        self.bytes_read, buf[0]
+                    )));
+                }
+                break;
+            }
+        }
+
+        // The line still counts, so later line numbers stay right
+        self.offset += 1;
+        self.report.stats.total_lines += 1;
+        Ok(())
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Tests
+// ─────────────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // =========================================================================
+    // quick_scan_for_diagrams() tests
+    // =========================================================================
+
+    #[test]
+    fn test_quick_scan_plain_text() {
+        let lines = vec![
+            "Hello world".to_string(),
+            "This is plain text".to_string(),
+            "No diagrams here".to_string(),
+        ];
+        let result = quick_scan_for_diagrams(&lines);
+
+        assert!(!result.likely_has_diagrams);
+        assert_eq!(result.lines_with_box_chars, 0);
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn test_quick_scan_with_diagram_lines() {
+        let lines = vec![
+            "+---+".to_string(),
+            "| a |".to_string(),
+            "+---+".to_string(),
+        ];
+        let result = quick_scan_for_diagrams(&lines);
+
+        assert!(result.likely_has_diagrams);
+        assert_eq!(result.regions, [0..3]);
+    }
+
+    #[test]
+    fn test_quick_scan_single_box_line_passes_through() {
+        let mut lines = vec!["plain text".to_string(); 100];
+        lines[0] = "+---+".to_string();
+        let result = quick_scan_for_diagrams(&lines);
+
+        assert_eq!(result.lines_scanned, 100);
+        assert_eq!(result.lines_with_box_chars, 1);
+        assert!(result.regions.is_empty());
+        assert!(!result.likely_has_diagrams);
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn test_quick_scan_covers_whole_input() {
+        let mut lines = vec!["plain text".to_string(); 5000];
+        lines[3000] = "┌───┐".to_string();
+        lines[3001] = "│ a │".to_string();
+        lines[3002] = "└───┘".to_string();
+        let result = quick_scan_for_diagrams(&lines);
+
+        assert_eq!(result.lines_scanned, 5000);
+        assert_eq!(result.regions, [3000..3003]);
+        assert!(result.likely_has_diagrams);
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn test_quick_scan_region_grouping() {
+        let lines =
+            to_lines("| a\nx\nx\nx\n| b\n\n\n\n\n----\n====\n\n\n\n\n+ c\nx\nx\nx\nx\n+ d\n");
+        let result = quick_scan_for_diagrams(&lines);
+
+        // Fill-only groups and lines more than three apart are not regions
+        assert_eq!(result.regions, [0..5]);
+    }
+
+    #[test]
+    fn test_box_content_decodes_multibyte_chars() {
+        assert_eq!(box_content("plain — text é"), BoxContent::None);
+        assert_eq!(box_content("é ═══"), BoxContent::Fill);
+        assert_eq!(box_content("日本 ║"), BoxContent::Border);
+    }
+
+    #[test]
+    fn test_corrector_finds_diagram_deep_in_long_input() {
+        let mut input: String = (0..3000).map(|i| format!("prose line {}\n", i)).collect();
+        input.push_str("+------+\n| hi|\n+------+\n");
+        let correction = Corrector::default().correct_str(&input);
+
+        assert!(!correction.report.passed_through());
+        assert!(
+            correction
+                .output
+                .ends_with("+------+\n| hi   |\n+------+\n")
+        );
+    }
+
+    // =========================================================================
+    // is_corner() tests - 13 corner characters
+    // =========================================================================
+
+    #[test]
+    fn test_is_corner_ascii() {
+        assert!(is_corner('+'), "ASCII plus should be corner");
+    }
+
+    #[test]
+    fn test_is_corner_light() {
+        assert!(is_corner('┌'), "light top-left corner");
+        assert!(is_corner('┐'), "light top-right corner");
+        assert!(is_corner('└'), "light bottom-left corner");
+        assert!(is_corner('┘'), "light bottom-right corner");
+    }
+
+    #[test]
+    fn test_is_corner_double() {
+        assert!(is_corner('╔'), "double top-left corner");
+        assert!(is_corner('╗'), "double top-right corner");
+        assert!(is_corner('╚'), "double bottom-left corner");
+        assert!(is_corner('╝'), "double bottom-right corner");
+    }
+
+    #[test]
+    fn test_is_corner_heavy_and_mixed() {
+        for c in ['┏', '┓', '┗', '┛', '┍', '┒', '╒', '╜'] {
+            assert!(is_corner(c), "{c} should be a corner");
+        }
+        assert!(is_junction('┣') && is_junction('╋') && is_junction('┿'));
+        assert!(is_box_char('╱'), "diagonals are box characters");
+        assert!(!is_border_char('╱'), "but cannot end a border");
+    }
+
+    #[test]
+    fn test_is_corner_rounded() {
+        assert!(is_corner('╭'), "rounded top-left corner");
+        assert!(is_corner('╮'), "rounded top-right corner");
+        assert!(is_corner('╯'), "rounded bottom-right corner");
+        assert!(is_corner('╰'), "rounded bottom-left corner");
+    }
+
+    #[test]
+    fn test_is_corner_negative() {
+        assert!(!is_corner('-'), "horizontal fill is not corner");
+        assert!(!is_corner('|'), "vertical border is not corner");
+        assert!(!is_corner('a'), "letter is not corner");
+        assert!(!is_corner(' '), "space is not corner");
+        assert!(!is_corner('─'), "horizontal line is not corner");
+        assert!(!is_corner('┼'), "junction is not corner");
+    }
+
+    // =========================================================================
+    // is_horizontal_fill() tests - 12 horizontal fill characters
+    // =========================================================================
+
+    #[test]
+    fn test_is_horizontal_fill_ascii() {
+        assert!(is_horizontal_fill('-'), "ASCII dash");
+        assert!(is_horizontal_fill('~'), "ASCII tilde");
+        assert!(is_horizontal_fill('='), "ASCII equals");
+    }
+
+    #[test]
+    fn test_is_horizontal_fill_light() {
+        assert!(is_horizontal_fill('─'), "light horizontal");
+        assert!(is_horizontal_fill('╌'), "light dashed 2");
+        assert!(is_horizontal_fill('┄'), "light dashed 3");
+        assert!(is_horizontal_fill('┈'), "light dashed 4");
+    }
+
+    #[test]
+    fn test_is_horizontal_fill_heavy() {
+        assert!(is_horizontal_fill('━'), "heavy horizontal");
+        assert!(is_horizontal_fill('╍'), "heavy dashed 2");
+        assert!(is_horizontal_fill('┅'), "heavy dashed 3");
+        assert!(is_horizontal_fill('┉'), "heavy dashed 4");
+    }
+
+    #[test]
+    fn test_is_horizontal_fill_double() {
+        assert!(is_horizontal_fill('═'), "double horizontal");
+    }
+
+    #[test]
+    fn test_is_horizontal_fill_negative() {
+        assert!(!is_horizontal_fill('|'), "vertical is not horizontal");
+        assert!(!is_horizontal_fill('+'), "corner is not horizontal fill");
+        assert!(!is_horizontal_fill('a'), "letter is not horizontal fill");
+        assert!(!is_horizontal_fill(' '), "space is not horizontal fill");
+        assert!(!is_horizontal_fill('│'), "vertical line is not horizontal");
+    }
+
+    // =========================================================================
+    // is_vertical_border() tests - 10 vertical border characters
+    // =========================================================================
+
+    #[test]
+    fn test_is_vertical_border_ascii() {
+        assert!(is_vertical_border('|'), "ASCII pipe");
+    }
+
+    #[test]
+    fn test_is_vertical_border_light() {
+        assert!(is_vertical_border('│'), "light vertical");
+        assert!(is_vertical_border('╎'), "light dashed 2");
+        assert!(is_vertical_border('┆'), "light dashed 3");
+        assert!(is_vertical_border('┊'), "light dashed 4");
+    }
+
+    #[test]
+    fn test_is_vertical_border_heavy() {
+        assert!(is_vertical_border('┃'), "heavy vertical");
+        assert!(is_vertical_border('╏'), "heavy dashed 2");
+        assert!(is_vertical_border('┇'), "heavy dashed 3");
+        assert!(is_vertical_border('┋'), "heavy dashed 4");
+    }
+
+    #[test]
+    fn test_is_vertical_border_double() {
+        assert!(is_vertical_border('║'), "double vertical");
+    }
+
+    #[test]
+    fn test_is_vertical_border_negative() {
+        assert!(!is_vertical_border('-'), "horizontal is not vertical");
+        assert!(!is_vertical_border('+'), "corner is not vertical border");
+        assert!(!is_vertical_border('a'), "letter is not vertical border");
+        assert!(!is_vertical_border(' '), "space is not vertical border");
+        assert!(!is_vertical_border('─'), "horizontal line is not vertical");
+    }
+
+    // =========================================================================
+    // is_junction() tests - 16 junction characters
+    // =========================================================================
+
+    #[test]
+    fn test_is_junction_light() {
+        assert!(is_junction('┬'), "light down and horizontal");
+        assert!(is_junction('┴'), "light up and horizontal");
+        assert!(is_junction('├'), "light vertical and right");
+        assert!(is_junction('┤'), "light vertical and left");
+        assert!(is_junction('┼'), "light vertical and horizontal");
+    }
+
+    #[test]
+    fn test_is_junction_double() {
+        assert!(is_junction('╦'), "double down and horizontal");
+        assert!(is_junction('╩'), "double up and horizontal");
+        assert!(is_junction('╠'), "double vertical and right");
+        assert!(is_junction('╣'), "double vertical and left");
+        assert!(is_junction('╬'), "double vertical and horizontal");
+    }
+
+    #[test]
+    fn test_is_junction_mixed() {
+        assert!(is_junction('╤'), "down single and horizontal double");
+        assert!(is_junction('╧'), "up single and horizontal double");
+        assert!(is_junction('╟'), "vertical double and right single");
+        assert!(is_junction('╢'), "vertical double and left single");
+        assert!(is_junction('╫'), "vertical double and horizontal single");
+        assert!(is_junction('╪'), "vertical single and horizontal double");
+    }
+
+    #[test]
+    fn test_is_junction_negative() {
+        assert!(!is_junction('+'), "ASCII plus is corner, not junction");
+        assert!(!is_junction('┌'), "corner is not junction");
+        assert!(!is_junction('─'), "horizontal is not junction");
+        assert!(!is_junction('│'), "vertical is not junction");
+        assert!(!is_junction('a'), "letter is not junction");
+    }
+
+    // =========================================================================
+    // is_box_char() tests - composite function
+    // =========================================================================
+
+    #[test]
+    fn test_is_box_char_corners() {
+        assert!(is_box_char('+'), "ASCII corner is box char");
+        assert!(is_box_char('┌'), "light corner is box char");
+        assert!(is_box_char('╔'), "double corner is box char");
+        assert!(is_box_char('╭'), "rounded corner is box char");
+    }
+
+    #[test]
+    fn test_is_box_char_horizontals() {
+        assert!(is_box_char('-'), "ASCII dash is box char");
+        assert!(is_box_char('─'), "light horizontal is box char");
+        assert!(is_box_char('═'), "double horizontal is box char");
+    }
+
+    #[test]
+    fn test_is_box_char_verticals() {
+        assert!(is_box_char('|'), "ASCII pipe is box char");
+        assert!(is_box_char('│'), "light vertical is box char");
+        assert!(is_box_char('║'), "double vertical is box char");
+    }
+
+    #[test]
+    fn test_is_box_char_junctions() {
+        assert!(is_box_char('┼'), "light junction is box char");
+        assert!(is_box_char('╬'), "double junction is box char");
+        assert!(is_box_char('╪'), "mixed junction is box char");
+    }
+
+    #[test]
+    fn test_is_box_char_negative() {
+        assert!(!is_box_char('a'), "letter is not box char");
+        assert!(!is_box_char(' '), "space is not box char");
+        assert!(!is_box_char('0'), "digit is not box char");
+        assert!(!is_box_char('\n'), "newline is not box char");
+        assert!(!is_box_char('中'), "CJK char is not box char");
+    }
+
+    // =========================================================================
+    // is_border_char() tests
+    // =========================================================================
+
+    #[test]
+    fn test_is_border_char_verticals() {
+        assert!(is_border_char('|'), "ASCII pipe is border char");
+        assert!(is_border_char('│'), "light vertical is border char");
+        assert!(is_border_char('║'), "double vertical is border char");
+    }
+
+    #[test]
+    fn test_is_border_char_corners() {
+        assert!(is_border_char('+'), "ASCII corner is border char");
+        assert!(is_border_char('┐'), "unicode corner is border char");
+        assert!(is_border_char('╝'), "double corner is border char");
+    }
+
+    #[test]
+    fn test_is_border_char_junctions() {
+        assert!(is_border_char('┤'), "junction is border char");
+        assert!(is_border_char('╣'), "double junction is border char");
+        assert!(is_border_char('╢'), "mixed junction is border char");
+    }
+
+    #[test]
+    fn test_is_border_char_negative() {
+        assert!(!is_border_char('-'), "horizontal fill is not border char");
+        assert!(!is_border_char('a'), "letter is not border char");
+        assert!(!is_border_char(' '), "space is not border char");
+    }
+
+    // =========================================================================
+    // detect_vertical_border() tests - frequency-based detection
+    // =========================================================================
+
+    #[test]
+    fn test_detect_vertical_border_ascii() {
+        let lines = vec!["| hello |", "| world |"];
+        assert_eq!(detect_vertical_border(&lines), '|');
+    }
+
+    #[test]
+    fn test_detect_vertical_border_unicode_light() {
+        let lines = vec!["│ hello │", "│ world │"];
+        assert_eq!(detect_vertical_border(&lines), '│');
+    }
+
+    #[test]
+    fn test_detect_vertical_border_unicode_double() {
+        let lines = vec!["║ hello ║", "║ world ║"];
+        assert_eq!(detect_vertical_border(&lines), '║');
+    }
+
+    #[test]
+    fn test_detect_vertical_border_mixed_prefers_most_common() {
+        let lines = vec!["│ a │", "│ b │", "│ c │", "| d |"];
+        // 6 occurrences of │ vs 2 occurrences of |
+        assert_eq!(detect_vertical_border(&lines), '│');
+    }
+
+    #[test]
+    fn test_detect_vertical_border_empty_defaults_to_ascii() {
+        let lines: Vec<&str> = vec![];
+        assert_eq!(detect_vertical_border(&lines), '|');
+    }
+
+    #[test]
+    fn test_detect_vertical_border_no_borders_defaults_to_ascii() {
+        let lines = vec!["hello world", "no borders here"];
+        assert_eq!(detect_vertical_border(&lines), '|');
+    }
+
+    // =========================================================================
+    // Revision::score() tests
+    // =========================================================================
+
+    fn make_analyzed_lines(lines: &[&str]) -> Vec<AnalyzedLine> {
+        lines.iter().map(|l| analyze_line(l)).collect()
+    }
+
+    #[test]
+    fn test_revision_score_pad_small_adjustment() {
+        let lines = vec!["| short|", "| longer |"];
+        let analyzed = make_analyzed_lines(&lines);
+        // Small padding (2 spaces) should have high score
+        let rev = Revision::PadBeforeSuffixBorder {
+            line_idx: 0,
+            spaces_to_add: 2,
+            target_column: 10,
+        };
+        let score = rev.score(&analyzed, 0);
+        // Base 0.8 - 0.2 penalty + 0.2 strong bonus = 0.8 for strong line
+        assert!(
+            (0.6..=1.0).contains(&score),
+            "score={} should be in [0.6, 1.0]",
+            score
+        );
+    }
+
+    #[test]
+    fn test_revision_score_pad_large_adjustment() {
+        let lines = vec!["| x|", "| very long content |"];
+        let analyzed = make_analyzed_lines(&lines);
+        // Large padding should have lower score
+        let rev = Revision::PadBeforeSuffixBorder {
+            line_idx: 0,
+            spaces_to_add: 10,
+            target_column: 20,
+        };
+        let score = rev.score(&analyzed, 0);
+        // 10 spaces = 1.0 penalty capped at 0.5, so 0.8 - 0.5 = 0.3 base
+        assert!(
+            (0.0..=0.8).contains(&score),
+            "large adjustment score={} should be lower",
+            score
+        );
+    }
+
+    #[test]
+    fn test_revision_score_pad_strong_line_bonus() {
+        let lines = vec!["+---+", "| x |"];
+        let analyzed = make_analyzed_lines(&lines);
+        let rev = Revision::PadBeforeSuffixBorder {
+            line_idx: 0,
+            spaces_to_add: 2,
+            target_column: 8,
+        };
+        let score = rev.score(&analyzed, 0);
+        // Strong line gets 0.2 bonus
+        assert!(score > 0.7, "strong line should get bonus, score={}", score);
+    }
+
+    #[test]
+    fn test_revision_score_add_border_base() {
+        let lines = vec!["| text", "| other |"];
+        let analyzed = make_analyzed_lines(&lines);
+        let rev = Revision::AddSuffixBorder {
+            line_idx: 0,
+            border_char: '|',
+            target_column: 10,
+        };
+        let score = rev.score(&analyzed, 0);
+        // AddSuffixBorder has base 0.5 + 0.1-0.2 strength bonus
+        assert!(
+            (0.5..=0.8).contains(&score),
+            "add border score={} should be moderate",
+            score
+        );
+    }
+
+    #[test]
+    fn test_revision_score_add_border_strong_line() {
+        let lines = vec!["+----", "+----+"];
+        let analyzed = make_analyzed_lines(&lines);
+        let rev = Revision::AddSuffixBorder {
+            line_idx: 0,
+            border_char: '+',
+            target_column: 6,
+        };
+        let score = rev.score(&analyzed, 0);
+        // Strong line gets 0.2 bonus instead of 0.1
+        assert!(
+            score >= 0.6,
+            "strong line add border score={} should be higher",
+            score
+        );
+    }
+
+    #[test]
+    fn test_revision_score_with_block_offset() {
+        // Test that block_start offset is correctly applied
+        let lines = vec!["| hello|", "| world |"];
+        let analyzed = make_analyzed_lines(&lines);
+        // Simulate being at block offset 5 in global lines
+        let rev = Revision::PadBeforeSuffixBorder {
+            line_idx: 5,
+            spaces_to_add: 2,
+            target_column: 10,
+        };
+        let score = rev.score(&analyzed, 5);
+        assert!(score > 0.0, "should correctly index with block offset");
+    }
+
+    // =========================================================================
+    // Revision::apply() tests
+    // =========================================================================
+
+    #[test]
+    fn test_revision_apply_pad_ascii() {
+        let mut lines = vec!["| short|".to_string()];
+        let rev = Revision::PadBeforeSuffixBorder {
+            line_idx: 0,
+            spaces_to_add: 3,
+            target_column: 10,
+        };
+        rev.apply(&mut lines);
+        assert_eq!(lines[0], "| short   |", "should pad before closing border");
+    }
+
+    #[test]
+    fn test_revision_apply_pad_unicode() {
+        let mut lines = vec!["│ text│".to_string()];
+        let rev = Revision::PadBeforeSuffixBorder {
+            line_idx: 0,
+            spaces_to_add: 2,
+            target_column: 10,
+        };
+        rev.apply(&mut lines);
+        assert_eq!(lines[0], "│ text  │", "should pad before unicode border");
+    }
+
+    #[test]
+    fn test_revision_apply_pad_corner() {
+        let mut lines = vec!["+---+".to_string()];
+        let rev = Revision::PadBeforeSuffixBorder {
+            line_idx: 0,
+            spaces_to_add: 2,
+            target_column: 7,
+        };
+        rev.apply(&mut lines);
+        assert_eq!(lines[0], "+---  +", "should pad before corner");
+    }
+
+    #[test]
+    fn test_revision_apply_pad_preserves_other_lines() {
+        let mut lines = vec!["| first|".to_string(), "| second |".to_string()];
+        let rev = Revision::PadBeforeSuffixBorder {
+            line_idx: 0,
+            spaces_to_add: 2,
+            target_column: 10,
+        };
+        rev.apply(&mut lines);
+        assert_eq!(lines[0], "| first  |");
+        assert_eq!(lines[1], "| second |", "other lines should be unchanged");
+    }
+
+    #[test]
+    fn test_revision_apply_add_border_ascii() {
+        let mut lines = vec!["| text".to_string()];
+        let rev = Revision::AddSuffixBorder {
+            line_idx: 0,
+            border_char: '|',
+            target_column: 10,
+        };
+        rev.apply(&mut lines);
+        assert_eq!(
+            lines[0], "| text    |",
+            "should add border at target column"
+        );
+    }
+
+    #[test]
+    fn test_revision_apply_add_border_unicode() {
+        let mut lines = vec!["│ hello".to_string()];
+        let rev = Revision::AddSuffixBorder {
+            line_idx: 0,
+            border_char: '│',
+            target_column: 12,
+        };
+        rev.apply(&mut lines);
+        assert_eq!(lines[0], "│ hello     │", "should add unicode border");
+    }
+
+    #[test]
+    fn test_revision_apply_add_corner() {
+        let mut lines = vec!["+----".to_string()];
+        let rev = Revision::AddSuffixBorder {
+            line_idx: 0,
+            border_char: '+',
+            target_column: 6,
+        };
+        rev.apply(&mut lines);
+        assert_eq!(lines[0], "+---- +", "should add corner");
+    }
+
+    #[test]
+    fn test_revision_apply_add_border_no_extra_padding() {
+        let mut lines = vec!["| exact len|".to_string()];
+        // If current width >= target, padding should be 0
+        let rev = Revision::AddSuffixBorder {
+            line_idx: 0,
+            border_char: '|',
+            target_column: 5, // Less than current width
+        };
+        rev.apply(&mut lines);
+        // Should add border with no padding
+        assert!(lines[0].ends_with('|'), "should still add border");
+    }
+
+    #[test]
+    fn test_revision_apply_extend_rule_ascii() {
+        let mut lines = vec!["+------+".to_string()];
+        let rev = Revision::ExtendHorizontalRule {
+            line_idx: 0,
+            fill_char: '-',
+            chars_to_add: 3,
+            target_column: 10,
+        };
+        rev.apply(&mut lines);
+        assert_eq!(lines[0], "+---------+");
+    }
+
+    #[test]
+    fn test_revision_apply_extend_rule_unicode_double() {
+        let mut lines = vec!["╚════╝  ".to_string()];
+        let rev = Revision::ExtendHorizontalRule {
+            line_idx: 0,
+            fill_char: '═',
+            chars_to_add: 2,
+            target_column: 7,
+        };
+        rev.apply(&mut lines);
+        assert_eq!(lines[0], "╚══════╝");
+    }
+
+    #[test]
+    fn test_revision_apply_remove_spaces() {
+        let mut lines = vec!["| short     |".to_string()];
+        let rev = Revision::RemoveSpacesBeforeSuffixBorder {
+            line_idx: 0,
+            spaces_to_remove: 3,
+            target_column: 9,
+        };
+        rev.apply(&mut lines);
+        assert_eq!(lines[0], "| short  |");
+    }
+
+    #[test]
+    fn test_revision_apply_remove_spaces_never_touches_content() {
+        let mut lines = vec!["| short  |".to_string()];
+        let rev = Revision::RemoveSpacesBeforeSuffixBorder {
+            line_idx: 0,
+            spaces_to_remove: 2,
+            target_column: 7,
+        };
+        rev.apply(&mut lines);
+        assert_eq!(lines[0], "| short  |", "at least one space must remain");
+    }
+
+    #[test]
+    fn test_revision_score_remove_spaces_below_pad() {
+        let analyzed = make_analyzed_lines(&["| short     |"]);
+        let pad = Revision::PadBeforeSuffixBorder {
+            line_idx: 0,
+            spaces_to_add: 2,
+            target_column: 14,
+        };
+        let shrink = Revision::RemoveSpacesBeforeSuffixBorder {
+            line_idx: 0,
+            spaces_to_remove: 2,
+            target_column: 10,
+        };
+        assert!(shrink.score(&analyzed, 0) < pad.score(&analyzed, 0));
+    }
+
+    #[test]
+    fn test_revision_apply_pad_interior_border() {
+        let mut lines = vec!["| ab|    | cd |".to_string()];
+        let rev = Revision::PadBeforeInteriorBorder {
+            line_idx: 0,
+            border_column: 4,
+            fill_char: ' ',
+            chars_to_add: 2,
+            target_column: 6,
+        };
+        rev.apply(&mut lines);
+        assert_eq!(lines[0], "| ab  |    | cd |");
+    }
+
+    #[test]
+    fn test_revision_apply_extend_interior_rule() {
+        let mut lines = vec!["└──┘   └────┘".to_string()];
+        let rev = Revision::PadBeforeInteriorBorder {
+            line_idx: 0,
+            border_column: 3,
+            fill_char: '─',
+            chars_to_add: 2,
+            target_column: 5,
+        };
+        rev.apply(&mut lines);
+        assert_eq!(lines[0], "└────┘   └────┘");
+    }
+
+    #[test]
+    fn test_revision_apply_interior_ignores_non_border_column() {
+        let mut lines = vec!["| ab|    | cd |".to_string()];
+        let rev = Revision::PadBeforeInteriorBorder {
+            line_idx: 0,
+            border_column: 2,
+            fill_char: ' ',
+            chars_to_add: 2,
+            target_column: 4,
+        };
+        rev.apply(&mut lines);
+        assert_eq!(lines[0], "| ab|    | cd |");
+    }
+
+    #[test]
+    fn test_revision_score_extend_rule() {
+        let analyzed = make_analyzed_lines(&["+---+"]);
+        let score = |chars_to_add| {
+            Revision::ExtendHorizontalRule {
+                line_idx: 0,
+                fill_char: '-',
+                chars_to_add,
+                target_column: 10,
+            }
+            .score(&analyzed, 0)
+        };
+        assert!(score(1) > score(5), "smaller extensions score higher");
+        assert!(
+            score(100) >= 0.5,
+            "long rules still pass the default threshold"
+        );
+    }
+
+    #[test]
+    fn test_detect_rule_fill() {
+        assert_eq!(detect_rule_fill("+------+"), Some('-'));
+        assert_eq!(detect_rule_fill("└──┴──┘  "), Some('─'));
+        assert_eq!(detect_rule_fill("╔═══╗"), Some('═'));
+        assert_eq!(detect_rule_fill("+---  +"), None);
+        assert_eq!(detect_rule_fill("| text |"), None);
+        assert_eq!(detect_rule_fill("+"), None);
+    }
+
+    #[test]
+    fn test_revision_apply_align_prefix_removes_indent() {
+        let mut lines = vec!["   | text |".to_string()];
+        let rev = Revision::AlignPrefixBorder {
+            line_idx: 0,
+            current_column: 3,
+            target_column: 1,
+        };
+        rev.apply(&mut lines);
+        assert_eq!(lines[0], " | text |");
+    }
+
+    #[test]
+    fn test_revision_apply_align_prefix_adds_indent() {
+        let mut lines = vec!["│ text │".to_string()];
+        let rev = Revision::AlignPrefixBorder {
+            line_idx: 0,
+            current_column: 0,
+            target_column: 2,
+        };
+        rev.apply(&mut lines);
+        assert_eq!(lines[0], "  │ text │");
+    }
+
+    #[test]
+    fn test_revision_score_align_prefix_prefers_small_drift() {
+        let analyzed = make_analyzed_lines(&["| x |"]);
+        let score = |current_column| {
+            Revision::AlignPrefixBorder {
+                line_idx: 0,
+                current_column,
+                target_column: 4,
+            }
+            .score(&analyzed, 0)
+        };
+        assert!((score(5) - 0.9).abs() < 1e-9, "one column drift");
+        assert!(score(3) > score(6));
+        assert!(score(0) < 0.5, "large shifts need a lower threshold");
+    }
+
+    #[test]
+    fn test_revision_score_terms_add_up_to_score() {
+        let analyzed = make_analyzed_lines(&["+------+", "| hi|"]);
+        let revisions = [
+            Revision::PadBeforeSuffixBorder {
+                line_idx: 1,
+                spaces_to_add: 3,
+                target_column: 7,
+            },
+            Revision::RemoveSpacesBeforeSuffixBorder {
+                line_idx: 0,
+                spaces_to_remove: 1,
+                target_column: 6,
+            },
+            Revision::AddSuffixBorder {
+                line_idx: 1,
+                border_char: '|',
+                target_column: 7,
+            },
+            Revision::ExtendHorizontalRule {
+                line_idx: 0,
+                fill_char: '-',
+                chars_to_add: 2,
+                target_column: 9,
+            },
+        ];
+        for revision in &revisions {
+            let sum: f64 = revision
+                .score_terms(&analyzed, 0)
+                .iter()
+                .map(|t| t.value)
+                .sum();
+            assert_eq!(sum, revision.score(&analyzed, 0), "{:?}", revision);
+        }
+
+        let labels: Vec<_> = revisions[1]
+            .score_terms(&analyzed, 0)
+            .iter()
+            .map(|t| t.label)
+            .collect();
+        assert_eq!(
+            labels,
+            [
+                "base",
+                "size penalty",
+                "strong line bonus",
+                "shrink penalty"
+            ]
+        );
+
+        // Large drifts push the sum below zero; the score is clamped
+        let drift = Revision::AlignPrefixBorder {
+            line_idx: 1,
+            current_column: 20,
+            target_column: 0,
+        };
+        let sum: f64 = drift
+            .score_terms(&analyzed, 0)
+            .iter()
+            .map(|t| t.value)
+            .sum();
+        assert!(sum < 0.0);
+        assert_eq!(drift.score(&analyzed, 0), 0.0);
+    }
+
+    // =========================================================================
+    // classify_line() tests
+    // =========================================================================
+
+    #[test]
+    fn test_explain_line_evidence() {
+        let evidence = explain_line("  +----+  ");
+        assert_eq!(evidence.kind, LineKind::Strong);
+        assert!(evidence.has_corner);
+        assert_eq!(evidence.total_chars, 6);
+
+        let evidence = explain_line("| text here");
+        assert_eq!(evidence.kind, LineKind::Weak);
+        assert!(evidence.starts_with_border && !evidence.ends_with_border);
+        assert_eq!((evidence.box_chars, evidence.total_chars), (1, 11));
+        assert!(!evidence.mostly_box_chars());
+
+        let evidence = explain_line("│ a │");
+        assert_eq!(evidence.kind, LineKind::Strong);
+        assert!(!evidence.has_corner && evidence.starts_with_border && evidence.ends_with_border);
+
+        assert_eq!(explain_line("plain").kind, LineKind::None);
+        assert_eq!(explain_line("   ").kind, LineKind::Blank);
+    }
+
+    #[test]
+    fn test_block_confidence_matches_detection() {
+        let lines: Vec<String> = [
+            "+----+",
+            "| a  |",
+            "| b and more",
+            "+----+",
+            "",
+            "",
+            "| some text",
+            "| more text",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        let blocks = find_diagram_blocks(&lines, true);
+        assert_eq!(blocks.len(), 2);
+        for block in &blocks {
+            assert_eq!(
+                BlockConfidence::of(&lines, block).confidence,
+                block.confidence
+            );
+        }
+
+        let first = BlockConfidence::of(&lines, &blocks[0]);
+        assert_eq!((first.strong, first.weak), (3, 1));
+        assert_eq!(first.strong_ratio, 0.75);
+        assert!((first.size_bonus - 0.2).abs() < 1e-9);
+
+        // Weak-only blocks fall below the threshold
+        assert!(blocks[1].confidence < MIN_BLOCK_CONFIDENCE);
+        let confident = find_diagram_blocks(&lines, false);
+        assert_eq!(confident.len(), 1);
+        assert_eq!(confident[0].start, blocks[0].start);
+    }
+
+    #[test]
+    fn test_classify_line_blank_empty() {
+        assert_eq!(classify_line(""), LineKind::Blank);
+    }
+
+    #[test]
+    fn test_classify_line_blank_spaces() {
+        assert_eq!(classify_line("   "), LineKind::Blank);
+        assert_eq!(classify_line("      "), LineKind::Blank);
+    }
+
+    #[test]
+    fn test_classify_line_blank_tabs() {
+        assert_eq!(classify_line("\t"), LineKind::Blank);
+        assert_eq!(classify_line("\t\t"), LineKind::Blank);
+    }
+
+    #[test]
+    fn test_classify_line_blank_mixed_whitespace() {
+        assert_eq!(classify_line("  \t  "), LineKind::Blank);
+    }
+
+    #[test]
+    fn test_classify_line_none_plain_text() {
+        assert_eq!(classify_line("hello world"), LineKind::None);
+        assert_eq!(classify_line("fn main() {}"), LineKind::None);
+    }
+
+    #[test]
+    fn test_classify_line_none_numbers() {
+        assert_eq!(classify_line("12345"), LineKind::None);
+        assert_eq!(classify_line("3.14159"), LineKind::None);
+    }
+
+    #[test]
+    fn test_classify_line_none_punctuation() {
+        assert_eq!(classify_line("..."), LineKind::None);
+        assert_eq!(classify_line("???"), LineKind::None);
+    }
+
+    #[test]
+    fn test_classify_line_strong_ascii_corners() {
+        assert_eq!(classify_line("+---+"), LineKind::Strong);
+        assert_eq!(classify_line("+--+"), LineKind::Strong);
+    }
+
+    #[test]
+    fn test_classify_line_strong_border_both_sides() {
+        assert_eq!(classify_line("| x |"), LineKind::Strong);
+        assert_eq!(classify_line("| content |"), LineKind::Strong);
+    }
+
+    #[test]
+    fn test_classify_line_strong_unicode_light() {
+        assert_eq!(classify_line("┌───┐"), LineKind::Strong);
+        assert_eq!(classify_line("│ y │"), LineKind::Strong);
+        assert_eq!(classify_line("└───┘"), LineKind::Strong);
+    }
+
+    #[test]
+    fn test_classify_line_strong_unicode_double() {
+        assert_eq!(classify_line("╔═══╗"), LineKind::Strong);
+        assert_eq!(classify_line("║ z ║"), LineKind::Strong);
+        assert_eq!(classify_line("╚═══╝"), LineKind::Strong);
+    }
+
+    #[test]
+    fn test_classify_line_strong_high_ratio() {
+        // More than 1/3 box chars = strong
+        assert_eq!(classify_line("---"), LineKind::Strong);
+        assert_eq!(classify_line("───────"), LineKind::Strong);
+    }
+
+    #[test]
+    fn test_classify_line_weak_few_box_chars() {
+        // Has box chars but doesn't meet strong criteria
+        assert_eq!(classify_line("text | here"), LineKind::Weak);
+        assert_eq!(classify_line("a - b"), LineKind::Weak);
+    }
+
+    #[test]
+    fn test_classify_line_weak_single_border() {
+        // Only one side has border
+        assert_eq!(classify_line("| text"), LineKind::Weak);
+        assert_eq!(classify_line("text |"), LineKind::Weak);
+    }
+
+    // =========================================================================
+    // visual_width() tests
+    // =========================================================================
+
+    #[test]
+    fn test_visual_width_empty() {
+        assert_eq!(visual_width(""), 0);
+    }
+
+    #[test]
+    fn test_visual_width_ascii() {
+        assert_eq!(visual_width("hello"), 5);
+        assert_eq!(visual_width("a b c"), 5);
+        assert_eq!(visual_width("test!"), 5);
+    }
+
+    #[test]
+    fn test_visual_width_box_chars() {
+        assert_eq!(visual_width("│──│"), 4);
+        assert_eq!(visual_width("┌──┐"), 4);
+        assert_eq!(visual_width("╔══╗"), 4);
+    }
+
+    #[test]
+    fn test_visual_width_cjk() {
+        // CJK characters are double-width
+        assert_eq!(visual_width("中"), 2);
+        assert_eq!(visual_width("中文"), 4);
+        assert_eq!(visual_width("日本語"), 6);
+    }
+
+    #[test]
+    fn test_visual_width_mixed_ascii_cjk() {
+        // "a中b" = 1 + 2 + 1 = 4
+        assert_eq!(visual_width("a中b"), 4);
+        assert_eq!(visual_width("hi中文"), 6); // 2 + 2 + 2
+    }
+
+    #[test]
+    fn test_visual_width_box_and_cjk() {
+        // Box chars in CJK context
+        assert_eq!(visual_width("│中│"), 4); // 1 + 2 + 1
+    }
+
+    #[test]
+    fn test_visual_width_narrow_symbols_above_u1100() {
+        assert_eq!(visual_width("→"), 1);
+        assert_eq!(visual_width("•"), 1);
+        assert_eq!(visual_width("…"), 1);
+    }
+
+    #[test]
+    fn test_visual_width_zero_width_marks() {
+        assert_eq!(visual_width("e\u{301}"), 1); // combining acute
+        assert_eq!(visual_width("a\u{200B}b"), 2); // zero-width space
+        assert_eq!(visual_width("\u{FE0F}"), 0); // lone variation selector
+    }
+
+    #[test]
+    fn test_visual_width_emoji_sequences() {
+        assert_eq!(visual_width("😀"), 2);
+        assert_eq!(visual_width("👩\u{200D}💻"), 2); // ZWJ sequence
+        assert_eq!(visual_width("👍🏽"), 2); // skin tone modifier
+    }
+
+    #[test]
+    fn test_visual_width_hangul() {
+        assert_eq!(visual_width("한"), 2); // precomposed syllable
+        assert_eq!(visual_width("\u{1112}\u{1161}\u{11AB}"), 2); // conjoining jamo
+    }
+
+    #[test]
+    fn test_visual_width_ambiguous_wide() {
+        let wide = AmbiguousWidth::Wide;
+        assert_eq!(visual_width_with("→", wide), 2);
+        assert_eq!(visual_width_with("│─│", wide), 3); // box chars stay narrow
+        assert_eq!(visual_width_with("┏━┓┗┛", wide), 5); // heavy corners are ambiguous
+        assert_eq!(char_width_with('•', wide), 2);
+        assert_eq!(expand_tabs_with("→\tx", 4, wide), "→  x");
+        assert_eq!(visual_width("→"), 1);
+    }
+
+    #[test]
+    fn test_corrector_heavy_box() {
+        let input = "┏━━━━━━┓\n┃ API┃\n┃ Rate ┃\n┗━━━━━━┛\n";
+        let correction = Corrector::default().correct_str(input);
+        assert_eq!(
+            correction.output,
+            "┏━━━━━━┓\n┃ API  ┃\n┃ Rate ┃\n┗━━━━━━┛\n"
+        );
+    }
+
+    #[test]
+    fn test_corrector_aligns_past_narrow_arrow() {
+        let input = "+---------+\n| a → b|\n+---------+\n";
+        let correction = Corrector::default().correct_str(input);
+        assert_eq!(correction.output, "+---------+\n| a → b   |\n+---------+\n");
+    }
+
+    #[test]
+    fn test_corrector_ambiguous_wide_option() {
+        let options = CorrectionOptions {
+            ambiguous_width: AmbiguousWidth::Wide,
+            ..Default::default()
+        };
+        let input = "+---------+\n| a → b|\n+---------+\n";
+        let correction = Corrector::new(options).correct_str(input);
+        assert_eq!(correction.output, "+---------+\n| a → b  |\n+---------+\n");
+    }
+
+    #[test]
+    fn test_expand_tabs_after_combining_mark() {
+        assert_eq!(expand_tabs("e\u{301}\tx", 4), "e\u{301}   x");
+    }
+
+    // =========================================================================
+    // analyze_line() tests
+    // =========================================================================
+
+    #[test]
+    fn test_analyze_line_blank() {
+        let result = analyze_line("");
+        assert_eq!(result.kind, LineKind::Blank);
+        assert_eq!(result.visual_width, 0);
+        assert!(result.suffix_border.is_none());
+    }
+
+    #[test]
+    fn test_analyze_line_strong_with_border() {
+        let result = analyze_line("| hello |");
+        assert_eq!(result.kind, LineKind::Strong);
+        assert_eq!(result.visual_width, 9);
+        assert!(result.suffix_border.is_some());
+        let border = result.suffix_border.unwrap();
+        assert_eq!(border.char, '|');
+    }
+
+    #[test]
+    fn test_analyze_line_indented() {
+        let result = analyze_line("  | text |");
+        assert_eq!(result.indent, 2);
+        assert_eq!(result.kind, LineKind::Strong);
+    }
+
+    #[test]
+    fn test_analyze_line_no_suffix_border() {
+        let result = analyze_line("| missing end");
+        assert_eq!(result.kind, LineKind::Weak);
+        assert!(result.suffix_border.is_none());
+    }
+
+    #[test]
+    fn test_analyze_line_unicode_border() {
+        let result = analyze_line("│ content │");
+        assert_eq!(result.kind, LineKind::Strong);
+        assert!(result.suffix_border.is_some());
+        let border = result.suffix_border.unwrap();
+        assert_eq!(border.char, '│');
+    }
+
+    // =========================================================================
+    // detect_suffix_border() tests
+    // =========================================================================
+
+    #[test]
+    fn test_detect_suffix_border_ascii_pipe() {
+        let border = detect_suffix_border("| hello |");
+        assert!(border.is_some());
+        let b = border.unwrap();
+        assert_eq!(b.char, '|');
+        assert!(!b.is_closing);
+        assert_eq!(b.column, 8);
+    }
+
+    #[test]
+    fn test_detect_suffix_border_unicode_light() {
+        let border = detect_suffix_border("│ text │");
+        assert!(border.is_some());
+        let b = border.unwrap();
+        assert_eq!(b.char, '│');
+        assert!(!b.is_closing);
+    }
+
+    #[test]
+    fn test_detect_suffix_border_corner() {
+        let border = detect_suffix_border("+---+");
+        assert!(border.is_some());
+        let b = border.unwrap();
+        assert_eq!(b.char, '+');
+        assert!(b.is_closing);
+    }
+
+    #[test]
+    fn test_detect_suffix_border_unicode_corner() {
+        let border = detect_suffix_border("┌───┐");
+        assert!(border.is_some());
+        let b = border.unwrap();
+        assert_eq!(b.char, '┐');
+        assert!(b.is_closing);
+    }
+
+    #[test]
+    fn test_detect_suffix_border_junction() {
+        let border = detect_suffix_border("│ a ┤");
+        assert!(border.is_some());
+        let b = border.unwrap();
+        assert_eq!(b.char, '┤');
+        assert!(b.is_closing);
+    }
+
+    #[test]
+    fn test_detect_suffix_border_none_no_border() {
+        let border = detect_suffix_border("hello world");
+        assert!(border.is_none());
+    }
+
+    #[test]
+    fn test_detect_suffix_border_none_empty() {
+        let border = detect_suffix_border("");
+        assert!(border.is_none());
+    }
+
+    #[test]
+    fn test_detect_suffix_border_trailing_spaces() {
+        // Should detect border despite trailing spaces
+        let border = detect_suffix_border("| text |   ");
+        assert!(border.is_some());
+        let b = border.unwrap();
+        assert_eq!(b.char, '|');
+    }
+
+    #[test]
+    fn test_detect_suffix_border_column_position() {
+        let border = detect_suffix_border("| ab |");
+        assert!(border.is_some());
+        let b = border.unwrap();
+        // "| ab |" has visual width 6, column of | is 5 (0-indexed)
+        assert_eq!(b.column, 5);
+    }
+
+    // =========================================================================
+    // detect_prefix_border() tests
+    // =========================================================================
+
+    #[test]
+    fn test_detect_prefix_border_vertical() {
+        let b = detect_prefix_border("  | text |").unwrap();
+        assert_eq!(b.column, 2);
+        assert_eq!(b.char, '|');
+        assert!(!b.is_corner);
+    }
+
+    #[test]
+    fn test_detect_prefix_border_corner() {
+        let b = detect_prefix_border("┌───┐").unwrap();
+        assert_eq!(b.column, 0);
+        assert_eq!(b.char, '┌');
+        assert!(b.is_corner);
+    }
+
+    #[test]
+    fn test_detect_prefix_border_none() {
+        assert!(detect_prefix_border("  text |").is_none());
+        assert!(detect_prefix_border("").is_none());
+        assert!(detect_prefix_border("    ").is_none());
+    }
+
+    #[test]
+    fn test_analyze_line_records_prefix_border() {
+        let analyzed = analyze_line("   │ x │");
+        assert_eq!(analyzed.indent, 3);
+        assert_eq!(analyzed.prefix_border.unwrap().column, 3);
+
+        assert!(analyze_line("plain text").prefix_border.is_none());
+    }
+
+    // =========================================================================
+    // expand_tabs() tests
+    // =========================================================================
+
+    #[test]
+    fn test_expand_tabs_start_of_line() {
+        assert_eq!(expand_tabs("\thello", 4), "    hello");
+    }
+
+    #[test]
+    fn test_expand_tabs_middle_of_line() {
+        assert_eq!(expand_tabs("a\tb", 4), "a   b");
+        assert_eq!(expand_tabs("ab\tc", 4), "ab  c");
+        assert_eq!(expand_tabs("abc\td", 4), "abc d");
+    }
+
+    #[test]
+    fn test_expand_tabs_multiple() {
+        assert_eq!(expand_tabs("\t\t", 4), "        ");
+        assert_eq!(expand_tabs("a\tb\tc", 4), "a   b   c");
+    }
+
+    #[test]
+    fn test_expand_tabs_width_2() {
+        assert_eq!(expand_tabs("\thello", 2), "  hello");
+        assert_eq!(expand_tabs("a\tb", 2), "a b");
+    }
+
+    #[test]
+    fn test_expand_tabs_width_8() {
+        assert_eq!(expand_tabs("\thello", 8), "        hello");
+    }
+
+    #[test]
+    fn test_expand_tabs_no_tabs() {
+        assert_eq!(expand_tabs("no tabs here", 4), "no tabs here");
+    }
+
+    #[test]
+    fn test_expand_tabs_empty() {
+        assert_eq!(expand_tabs("", 4), "");
+    }
+
+    #[test]
+    fn test_expand_tabs_with_cjk() {
+        // CJK character "中" has visual width 2, so:
+        // "中\tx" with tab_width=4: col starts at 0, "中" takes cols 0-1 (width 2),
+        // tab at col 2 should expand to 2 spaces to reach col 4
+        assert_eq!(expand_tabs("中\tx", 4), "中  x");
+
+        // "a中\tx" with tab_width=4: "a" at col 0 (width 1), "中" at cols 1-2 (width 2),
+        // col is now 3, tab expands to 1 space to reach col 4
+        assert_eq!(expand_tabs("a中\tx", 4), "a中 x");
+
+        // "中中\tx" with tab_width=4: two CJK chars = width 4, col is 4,
+        // tab at col 4 expands to 4 spaces to reach col 8
+        assert_eq!(expand_tabs("中中\tx", 4), "中中    x");
+    }
+
+    // =========================================================================
+    // find_diagram_blocks() tests
+    // =========================================================================
+
+    #[test]
+    fn test_find_diagram_blocks_simple() {
+        let lines: Vec<String> = vec![
+            "Some text".to_string(),
+            "+---+".to_string(),
+            "| x |".to_string(),
+            "+---+".to_string(),
+            "More text".to_string(),
+        ];
+
+        let blocks = find_diagram_blocks(&lines, false);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].start, 1);
+        assert_eq!(blocks[0].end, 4);
+    }
+
+    #[test]
+    fn test_find_diagram_blocks_no_diagrams() {
+        let lines: Vec<String> = vec![
+            "Just plain text".to_string(),
+            "No diagrams here".to_string(),
+            "More text".to_string(),
+        ];
+
+        let blocks = find_diagram_blocks(&lines, false);
+        assert_eq!(blocks.len(), 0);
+    }
+
+    #[test]
+    fn test_find_diagram_blocks_multiple() {
+        // Need more than 3 non-boxy lines to prevent lookahead merging
+        let lines: Vec<String> = vec![
+            "+--+".to_string(),
+            "| A|".to_string(),
+            "+--+".to_string(),
+            "plain text".to_string(),
+            "more text".to_string(),
+            "even more".to_string(),
+            "still more".to_string(),
+            "+--+".to_string(),
+            "| B|".to_string(),
+            "+--+".to_string(),
+        ];
+
+        let blocks = find_diagram_blocks(&lines, false);
+        assert_eq!(blocks.len(), 2, "should find two separate blocks");
+        assert_eq!(blocks[0].start, 0);
+        assert_eq!(blocks[0].end, 3);
+        assert_eq!(blocks[1].start, 7);
+        assert_eq!(blocks[1].end, 10);
+    }
+
+    #[test]
+    fn test_find_diagram_blocks_with_blank_gap() {
+        let lines: Vec<String> = vec![
+            "+---+".to_string(),
+            "| a |".to_string(),
+            "".to_string(), // Single blank allowed
+            "| b |".to_string(),
+            "+---+".to_string(),
+        ];
+
+        let blocks = find_diagram_blocks(&lines, false);
+        assert_eq!(blocks.len(), 1, "single blank gap should be allowed");
+        assert_eq!(blocks[0].start, 0);
+        assert_eq!(blocks[0].end, 5);
+    }
+
+    #[test]
+    fn test_find_diagram_blocks_large_gap_splits() {
+        let lines: Vec<String> = vec![
+            "+--+".to_string(),
+            "| A|".to_string(),
+            "+--+".to_string(),
+            "".to_string(),
+            "".to_string(), // Two blank lines should split
+            "+--+".to_string(),
+            "| B|".to_string(),
+            "+--+".to_string(),
+        ];
+
+        let blocks = find_diagram_blocks(&lines, false);
+        assert_eq!(blocks.len(), 2, "double blank gap should split blocks");
+    }
+
+    #[test]
+    fn test_find_box_regions_side_by_side() {
+        let lines = [
+            "+------+    +--------+",
+            "| a|    | b |",
+            "+------+    +--------+",
+        ];
+        assert_eq!(
+            find_box_regions(&lines),
+            vec![
+                BoxRegion { left: 0, right: 7 },
+                BoxRegion {
+                    left: 12,
+                    right: 21
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_box_regions_merges_nested_and_drifted_rules() {
+        let lines = [
+            "+------------+",
+            "|  +------+  |",
+            "|  +------+  |",
+            "+-----------+",
+        ];
+        assert_eq!(
+            find_box_regions(&lines),
+            vec![BoxRegion { left: 0, right: 13 }]
+        );
+    }
+
+    #[test]
+    fn test_find_box_regions_ignores_rows_and_arrows() {
+        assert!(find_box_regions(&["| a |   | b |", "  ---> ", "+ -"]).is_empty());
+    }
+
+    #[test]
+    fn test_find_diagram_blocks_unicode() {
+        let lines: Vec<String> = vec![
+            "┌───┐".to_string(),
+            "│ x │".to_string(),
+            "└───┘".to_string(),
+        ];
+
+        let blocks = find_diagram_blocks(&lines, false);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].start, 0);
+        assert_eq!(blocks[0].end, 3);
+    }
+
+    #[test]
+    fn test_find_diagram_blocks_at_start() {
+        let lines: Vec<String> = vec!["+--+".to_string(), "|xy|".to_string(), "+--+".to_string()];
+
+        let blocks = find_diagram_blocks(&lines, false);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].start, 0);
+    }
+
+    #[test]
+    fn test_find_diagram_blocks_at_end() {
+        let lines: Vec<String> = vec![
+            "text".to_string(),
+            "+--+".to_string(),
+            "|xy|".to_string(),
+            "+--+".to_string(),
+        ];
+
+        let blocks = find_diagram_blocks(&lines, false);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].end, 4, "should go to end of lines");
+    }
+
+    #[test]
+    fn test_find_diagram_blocks_confidence_high() {
+        let lines: Vec<String> = vec![
+            "+------+".to_string(),
+            "| text |".to_string(),
+            "| more |".to_string(),
+            "+------+".to_string(),
+        ];
+
+        let blocks = find_diagram_blocks(&lines, false);
+        assert_eq!(blocks.len(), 1);
+        assert!(
+            blocks[0].confidence > 0.5,
+            "all strong lines should have high confidence"
+        );
+    }
+
+    #[test]
+    fn test_find_diagram_blocks_all_flag() {
+        let lines: Vec<String> = vec![
+            "text | here".to_string(), // Weak line
+            "more".to_string(),
+        ];
+
+        // Without all_blocks flag, low confidence blocks are skipped
+        let blocks_default = find_diagram_blocks(&lines, false);
+
+        // With all_blocks flag, low confidence blocks are included
+        let blocks_all = find_diagram_blocks(&lines, true);
+
+        assert!(
+            blocks_all.len() >= blocks_default.len(),
+            "all_blocks=true should include more blocks"
+        );
+    }
+
+    #[test]
+    fn test_find_diagram_blocks_trims_trailing_blank() {
+        let lines: Vec<String> = vec![
+            "+--+".to_string(),
+            "|ab|".to_string(),
+            "+--+".to_string(),
+            "".to_string(), // Trailing blank
+        ];
+
+        let blocks = find_diagram_blocks(&lines, false);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].end, 3, "should trim trailing blank");
+    }
+
+    #[test]
+    fn test_find_diagram_blocks_empty_input() {
+        let lines: Vec<String> = vec![];
+        let blocks = find_diagram_blocks(&lines, false);
+        assert_eq!(blocks.len(), 0);
+    }
+
+    #[test]
+    fn test_find_diagram_blocks_only_blanks() {
+        let lines: Vec<String> = vec!["".to_string(), "   ".to_string(), "".to_string()];
+        let blocks = find_diagram_blocks(&lines, false);
+        assert_eq!(blocks.len(), 0);
+    }
+
+    // =========================================================================
+    // detect_suffix_border() tests (old location kept for reference)
+    // =========================================================================
+
+    #[test]
+    fn test_detect_suffix_border() {
+        let border = detect_suffix_border("| hello |");
+        assert!(border.is_some());
+        let b = border.unwrap();
+        assert_eq!(b.char, '|');
+        assert!(!b.is_closing);
+
+        let no_border = detect_suffix_border("hello world");
+        assert!(no_border.is_none());
+    }
+
+    // =========================================================================
+    // Line range parsing tests
+    // =========================================================================
+
+    #[test]
+    fn test_parse_simple_range() {
+        let ranges = parse_line_ranges("10-50").unwrap();
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0].start, 10);
+        assert_eq!(ranges[0].end, 50);
+    }
+
+    #[test]
+    fn test_parse_multiple_ranges() {
+        let ranges = parse_line_ranges("1-10, 20-30, 50-60").unwrap();
+        assert_eq!(ranges.len(), 3);
+        assert_eq!(ranges[0], LineRange { start: 1, end: 10 });
+        assert_eq!(ranges[1], LineRange { start: 20, end: 30 });
+        assert_eq!(ranges[2], LineRange { start: 50, end: 60 });
+    }
+
+    #[test]
+    fn test_parse_open_ended_start() {
+        let ranges = parse_line_ranges("50-").unwrap();
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0].start, 50);
+        assert_eq!(ranges[0].end, usize::MAX);
+    }
+
+    #[test]
+    fn test_parse_open_ended_end() {
+        let ranges = parse_line_ranges("-100").unwrap();
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0].start, 1);
+        assert_eq!(ranges[0].end, 100);
+    }
+
+    #[test]
+    fn test_parse_single_line() {
+        let ranges = parse_line_ranges("42").unwrap();
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0].start, 42);
+        assert_eq!(ranges[0].end, 42);
+    }
+
+    #[test]
+    fn test_merge_overlapping_ranges() {
+        let ranges = parse_line_ranges("1-50, 40-100").unwrap();
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0].start, 1);
+        assert_eq!(ranges[0].end, 100);
+    }
+
+    #[test]
+    fn test_merge_adjacent_ranges() {
+        let ranges = parse_line_ranges("1-10, 11-20").unwrap();
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0].start, 1);
+        assert_eq!(ranges[0].end, 20);
+    }
+
+    #[test]
+    fn test_invalid_range_reversed() {
+        let result = parse_line_ranges("50-10");
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("start (50) > end (10)"));
+    }
+
+    #[test]
+    fn test_invalid_range_non_numeric() {
+        let result = parse_line_ranges("abc");
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("Invalid line number"));
+    }
+
+    #[test]
+    fn test_invalid_range_zero() {
+        let result = parse_line_ranges("0-10");
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("Line numbers start at 1"));
+    }
+
+    #[test]
+    fn test_line_in_ranges() {
+        let ranges = vec![
+            LineRange { start: 1, end: 10 },
+            LineRange { start: 20, end: 30 },
+        ];
+        assert!(line_in_ranges(5, &ranges));
+        assert!(line_in_ranges(1, &ranges));
+        assert!(line_in_ranges(10, &ranges));
+        assert!(line_in_ranges(25, &ranges));
+        assert!(!line_in_ranges(15, &ranges));
+        assert!(!line_in_ranges(31, &ranges));
+    }
+
+    #[test]
+    fn test_block_overlaps_ranges() {
+        let ranges = vec![LineRange { start: 10, end: 20 }];
+
+        // Block fully inside range
+        let block_inside = DiagramBlock {
+            start: 11, // 0-indexed, so line 12
+            end: 15,   // exclusive, so through line 15
+            confidence: 1.0,
+            forced: false,
+            min_score: None,
+        };
+        assert!(block_overlaps_ranges(&block_inside, &ranges));
+
+        // Block overlapping start of range
+        let block_overlap_start = DiagramBlock {
+            start: 5,
+            end: 12,
+            confidence: 1.0,
+            forced: false,
+            min_score: None,
+        };
+        assert!(block_overlaps_ranges(&block_overlap_start, &ranges));
+
+        // Block overlapping end of range
+        let block_overlap_end = DiagramBlock {
+            start: 18,
+            end: 25,
+            confidence: 1.0,
+            forced: false,
+            min_score: None,
+        };
+        assert!(block_overlaps_ranges(&block_overlap_end, &ranges));
+
+        // Block completely outside range
+        let block_outside = DiagramBlock {
+            start: 25,
+            end: 30,
+            confidence: 1.0,
+            forced: false,
+            min_score: None,
+        };
+        assert!(!block_overlaps_ranges(&block_outside, &ranges));
+    }
+
+    // =========================================================================
+    // Left-border alignment tests
+    // =========================================================================
+
+    fn correct_all(lines: &[&str]) -> Vec<String> {
+        let mut lines: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+        let block = DiagramBlock {
+            start: 0,
+            end: lines.len(),
+            confidence: 1.0,
+            forced: false,
+            min_score: None,
+        };
+        correct_block(&mut lines, &block, &CorrectionOptions::default());
+        lines
+    }
+
+    #[test]
+    fn test_correct_block_realigns_drifted_left_borders() {
+        let corrected = correct_all(&["  +------+", "   | ab |", " | cd   |", "  +------+"]);
+        assert_eq!(
+            corrected,
+            vec!["  +------+", "  | ab   |", "  | cd   |", "  +------+"]
+        );
+    }
+
+    #[test]
+    fn test_correct_block_extends_short_rules_with_fill() {
+        let corrected = correct_all(&["╔═══╗", "║ wide ║", "╚═══╝"]);
+        assert_eq!(corrected, vec!["╔══════╗", "║ wide ║", "╚══════╝"]);
+    }
+
+    #[test]
+    fn test_correct_block_aligns_side_by_side_boxes_independently() {
+        let corrected = correct_all(&[
+            "+------+    +--------+",
+            "| a|    | b |",
+            "| c     |    | d   |",
+            "+------+    +--------+",
+        ]);
+        assert_eq!(
+            corrected,
+            vec![
+                "+-------+    +--------+",
+                "| a     |    | b      |",
+                "| c     |    | d      |",
+                "+-------+    +--------+",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_correct_block_aligns_boxes_of_different_heights() {
+        let corrected = correct_all(&[
+            "┌────┐   ┌──────┐",
+            "│ a│   │ b    │",
+            "└────┘   │ c  │",
+            "         └──────┘",
+        ]);
+        assert_eq!(
+            corrected,
+            vec![
+                "┌────┐   ┌──────┐",
+                "│ a  │   │ b    │",
+                "└────┘   │ c    │",
+                "         └──────┘",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_correct_block_leaves_connectors_under_side_by_side_boxes() {
+        let input = [
+            "+----+   +----+",
+            "| a  |   | b  |",
+            "+----+   +----+",
+            "  |        |",
+            "  v        v",
+        ];
+        assert_eq!(correct_all(&input), input);
+    }
+
+    fn correct_all_shrinking(lines: &[&str]) -> Vec<String> {
+        let mut lines: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+        let block = DiagramBlock {
+            start: 0,
+            end: lines.len(),
+            confidence: 1.0,
+            forced: false,
+            min_score: None,
+        };
+        let options = CorrectionOptions {
+            allow_shrink: true,
+            ..Default::default()
+        };
+        let result = correct_block(&mut lines, &block, &options);
+        assert_eq!(result.revisions_shrunk, 1);
+        lines
+    }
+
+    #[test]
+    fn test_correct_block_shrinks_to_corners() {
+        let corrected =
+            correct_all_shrinking(&["+--------+", "| short     |", "| text   |", "+--------+"]);
+        assert_eq!(
+            corrected,
+            vec!["+--------+", "| short  |", "| text   |", "+--------+"]
+        );
+    }
+
+    #[test]
+    fn test_correct_block_shrinks_to_majority_without_corners() {
+        let corrected = correct_all_shrinking(&["| a    |", "| b    |", "| c      |"]);
+        assert_eq!(corrected, vec!["| a    |", "| b    |", "| c    |"]);
+    }
+
+    #[test]
+    fn test_correct_block_shrink_stops_at_content() {
+        let corrected =
+            correct_all_shrinking(&["+------+", "| longer text   |", "| ab   |", "+------+"]);
+        assert_eq!(
+            corrected,
+            vec![
+                "+-------------+",
+                "| longer text |",
+                "| ab          |",
+                "+-------------+",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_correct_block_insert_only_by_default() {
+        let corrected = correct_all(&["+--------+", "| short     |", "+--------+"]);
+        assert_eq!(
+            corrected,
+            vec!["+-----------+", "| short     |", "+-----------+"]
+        );
+    }
+
+    #[test]
+    fn test_correct_block_realigns_drifted_bottom_edge() {
+        let corrected = correct_all(&["┌────┐", "│ ab │", " └────┘"]);
+        assert_eq!(corrected, vec!["┌────┐", "│ ab │", "└────┘"]);
+    }
+
+    #[test]
+    fn test_correct_block_keeps_rows_after_separator_in_box() {
+        let corrected = correct_all(&["+---+", "| a |", "+---+", " | b |", "+---+"]);
+        assert_eq!(corrected[3], "| b |");
+    }
+
+    #[test]
+    fn test_correct_block_leaves_connectors_between_boxes() {
+        let input = [
+            "+---+", "| a |", "+---+", "    |", "+---+", "| b |", "+---+",
+        ];
+        let corrected = correct_all(&input);
+        assert_eq!(corrected[3], "    |", "connector must not be re-indented");
+    }
+
+    #[test]
+    fn test_correct_block_left_alignment_ignores_tables_without_corners() {
+        let input = ["| a | b |", " |---|---|", "| c | d |"];
+        let corrected = correct_all(&input);
+        assert_eq!(corrected[1], " |---|---|");
+    }
+
+    // =========================================================================
+    // Corrector tests
+    // =========================================================================
+
+    #[test]
+    fn test_corrector_correct_str_aligns_and_reports() {
+        let corrector = Corrector::new(CorrectionOptions::default());
+        let correction = corrector.correct_str("+------+\n| hi|\n+------+\n");
+
+        assert!(correction.changed);
+        assert_eq!(correction.output, "+------+\n| hi   |\n+------+\n");
+        assert_eq!(correction.report.stats.blocks_found, 1);
+        assert_eq!(correction.report.stats.blocks_modified, 1);
+        assert_eq!(correction.report.blocks.len(), 1);
+
+        let block = &correction.report.blocks[0];
+        assert_eq!((block.block.start, block.block.end), (0, 3));
+        let result = block.result.as_ref().expect("block should be corrected");
+        assert_eq!(result.iterations, vec![1]);
+        assert!(result.converged);
+    }
+
+    #[test]
+    fn test_corrector_correct_str_preserves_missing_final_newline() {
+        let corrector = Corrector::default();
+        let correction = corrector.correct_str("+------+\n| hi|\n+------+");
+        assert_eq!(correction.output, "+------+\n| hi   |\n+------+");
+    }
+
+    #[test]
+    fn test_corrector_correct_str_preserves_crlf() {
+        let corrector = Corrector::default();
+        let aligned = "+------+\r\n| hi   |\r\n+------+\r\n";
+        let correction = corrector.correct_str(aligned);
+        assert!(!correction.changed);
+        assert_eq!(correction.output, aligned);
+
+        let correction = corrector.correct_str("+------+\r\n| hi|\n+------+\r\n");
+        assert!(correction.changed);
+        assert_eq!(correction.output, "+------+\r\n| hi   |\n+------+\r\n");
+    }
+
+    #[test]
+    fn test_corrector_unchanged_input_round_trips() {
+        let corrector = Corrector::default();
+        let input = "plain text\nwith no diagrams\n";
+        let correction = corrector.correct_str(input);
+
+        assert!(!correction.changed);
+        assert_eq!(correction.output, input);
+        assert!(correction.report.passed_through());
+        assert!(correction.report.blocks.is_empty());
+    }
+
+    #[test]
+    fn test_corrector_reports_blocks_outside_ranges_as_skipped() {
+        let options = CorrectionOptions {
+            lines: Some(vec![LineRange { start: 1, end: 1 }]),
+            ..Default::default()
+        };
+        let corrector = Corrector::new(options);
+        let lines = vec![
+            "prose".to_string(),
+            "+------+".to_string(),
+            "| hi|".to_string(),
+            "+------+".to_string(),
+        ];
+
+        let (corrected, report) = corrector.correct_lines(lines.clone());
+        assert_eq!(corrected, lines);
+        assert_eq!(report.stats.blocks_skipped, 1);
+        assert!(report.blocks[0].result.is_none());
+    }
+
+    // =========================================================================
+    // Markdown tests
+    // =========================================================================
+
+    fn to_lines(text: &str) -> Vec<String> {
+        text.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_find_fenced_blocks_backticks_and_tildes() {
+        let lines = to_lines("prose\n```\na\n```\n~~~text\nb\nc\n~~~\n");
+        assert_eq!(find_fenced_blocks(&lines, None), vec![2..3, 5..7]);
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn test_find_fenced_blocks_closing_fence_rules() {
+        // A shorter fence or one of the other character does not close
+        let lines = to_lines("````\n```\n~~~~\n`````\nafter");
+        assert_eq!(find_fenced_blocks(&lines, None), [1..3]);
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn test_find_fenced_blocks_unclosed_runs_to_end() {
+        let lines = to_lines("```\na\nb");
+        assert_eq!(find_fenced_blocks(&lines, None), [1..3]);
+    }
+
+    #[test]
+    fn test_find_fenced_blocks_ignores_non_fences() {
+        let lines = to_lines("    ```\ncode\n``x`\n`` inline ``\n");
+        assert!(find_fenced_blocks(&lines, None).is_empty());
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn test_find_fenced_blocks_info_filter() {
+        let lines = to_lines("```rust\na\n```\n``` Diagram title\nb\n```\n```\nc\n```");
+        let filter = vec!["text".to_string(), "diagram".to_string()];
+        assert_eq!(find_fenced_blocks(&lines, Some(&filter)), [4..5]);
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn test_find_fenced_blocks_info_filter_matches_language_only() {
+        let lines = to_lines("```text title=\"x\"\na\n```\n```textile\nb\n```");
+        let filter = vec!["text".to_string()];
+        assert_eq!(find_fenced_blocks(&lines, Some(&filter)), [1..2]);
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn test_corrector_markdown_only_touches_fences() {
+        let input = "| a | b |\n|---|----|\n\n+------+\n| hi|\n+------+\n\n\tx\n```text\n+------+\n| hi|\n+------+\n```\n";
+        let options = CorrectionOptions {
+            markdown: true,
+            ..Default::default()
+        };
+        let correction = Corrector::new(options).correct_str(input);
+        assert_eq!(
+            correction.output,
+            "| a | b |\n|---|----|\n\n+------+\n| hi|\n+------+\n\n\tx\n```text\n+------+\n| hi   |\n+------+\n```\n"
+        );
+        assert_eq!(correction.report.fences, Some(vec![9..12]));
+        assert_eq!(correction.report.stats.blocks_found, 1);
+        assert_eq!(correction.report.blocks[0].block.start, 9);
+    }
+
+    fn correct_with_tabs(input: &str, tabs: TabExpansion) -> String {
+        let options = CorrectionOptions {
+            tabs,
+            ..Default::default()
+        };
+        Corrector::new(options).correct_str(input).output
+    }
+
+    #[test]
+    fn test_tabs_expand_blocks_leaves_prose_tabs() {
+        let input = "\t+---+\n\t| a|\n\t+---+\nprose\tx\n";
+        assert_eq!(
+            correct_with_tabs(input, TabExpansion::ExpandBlocks),
+            "    +---+\n    | a |\n    +---+\nprose\tx\n"
+        );
+    }
+
+    #[test]
+    fn test_tabs_expand_blocks_skips_aligned_blocks() {
+        let input = "\t+---+\n\t| a |\n\t+---+\n";
+        let options = CorrectionOptions {
+            tabs: TabExpansion::ExpandBlocks,
+            ..Default::default()
+        };
+        let correction = Corrector::new(options).correct_str(input);
+        assert_eq!(correction.output, input);
+        assert!(!correction.changed);
+        assert_eq!(correction.report.stats.blocks_found, 1);
+    }
+
+    #[test]
+    fn test_tabs_preserve_keeps_unmodified_lines() {
+        let input = "\t+---+\n\t| a|\n\t+---+\nprose\tx\n";
+        assert_eq!(
+            correct_with_tabs(input, TabExpansion::Preserve),
+            "\t+---+\n    | a |\n\t+---+\nprose\tx\n"
+        );
+    }
+
+    #[test]
+    fn test_tabs_expand_all_expands_everything() {
+        let input = "\t+---+\n\t| a|\n\t+---+\nprose\tx\n";
+        assert_eq!(
+            correct_with_tabs(input, TabExpansion::ExpandAll),
+            "    +---+\n    | a |\n    +---+\nprose   x\n"
+        );
+    }
+
+    #[test]
+    fn test_corrector_markdown_without_fences_passes_through() {
+        let options = CorrectionOptions {
+            markdown: true,
+            ..Default::default()
+        };
+        let input = "+------+\n| hi|\n+------+\n";
+        let correction = Corrector::new(options).correct_str(input);
+        assert!(!correction.changed);
+        assert!(correction.report.passed_through());
+    }
+
+    // =========================================================================
+    // Streaming tests
+    // =========================================================================
+
+    fn correct_streamed(corrector: &Corrector, input: &str) -> (String, StreamCorrection) {
+        let mut output = Vec::new();
+        let result = corrector
+            .correct_stream(input.as_bytes(), &mut output)
+            .unwrap();
+        (String::from_utf8(output).unwrap(), result)
+    }
+
+    fn long_document() -> String {
+        let mut input = String::new();
+        for section in 0..4 {
+            for i in 0..1500 {
+                input.push_str(&format!("prose {section}.{i}\n"));
+            }
+            input.push_str("+------+\n| hi|\n\n| there |\n+------+\n");
+        }
+        input
+    }
+
+    #[test]
+    fn test_correct_stream_matches_correct_str() {
+        let corrector = Corrector::default();
+        let input = long_document();
+        let (output, streamed) = correct_streamed(&corrector, &input);
+        let whole = corrector.correct_str(&input);
+
+        assert_eq!(output, whole.output);
+        assert!(streamed.changed);
+        assert_eq!(streamed.report.stats.blocks_found, 4);
+        assert_eq!(streamed.report.stats.total_lines, 6020);
+        let starts: Vec<usize> = streamed
+            .report
+            .blocks
+            .iter()
+            .map(|b| b.block.start)
+            .collect();
+        let expected: Vec<usize> = whole.report.blocks.iter().map(|b| b.block.start).collect();
+        assert_eq!(starts, expected);
+        assert_eq!(streamed.report.quick_scan.unwrap().regions.len(), 4);
+    }
+
+    #[test]
+    fn test_correct_stream_preserves_line_endings() {
+        let corrector = Corrector::default();
+        let (output, streamed) = correct_streamed(&corrector, "\u{FEFF}+---+\r\n| a|\n+---+");
+        assert_eq!(output, "\u{FEFF}+---+\r\n| a |\n+---+");
+        assert!(streamed.changed);
+
+        let (output, streamed) = correct_streamed(&corrector, "plain\r\ntext\n");
+        assert_eq!(output, "plain\r\ntext\n");
+        assert!(!streamed.changed);
+        assert!(streamed.report.passed_through());
+    }
+
+    #[test]
+    fn test_correct_stream_directives_span_segments() {
+        let mut input = "# aadc: ignore-next-block\n".to_string();
+        input.push_str(&"prose\n".repeat(3000));
+        input.push_str("+------+\n| hi|\n+------+\n");
+        let (output, streamed) = correct_streamed(&Corrector::default(), &input);
+
+        assert_eq!(output, input);
+        assert!(!streamed.changed);
+    }
+
+    #[test]
+    fn test_correct_stream_shifts_line_ranges() {
+        let input = long_document();
+        let options = CorrectionOptions {
+            lines: Some(parse_line_ranges("3000-3020").unwrap()),
+            ..Default::default()
+        };
+        let (_, streamed) = correct_streamed(&Corrector::new(options), &input);
+
+        let processed: Vec<usize> = streamed
+            .report
+            .blocks
+            .iter()
+            .filter(|b| b.result.is_some())
+            .map(|b| b.block.start)
+            .collect();
+        assert_eq!(processed, [3005]);
+        assert_eq!(streamed.report.stats.blocks_skipped, 3);
+    }
+
+    #[test]
+    fn test_correct_stream_passes_long_lines_through() {
+        // A long line of multibyte characters, so chunks split them
+        let long = format!("x{}", "é".repeat(STREAM_MAX_LINE_BYTES));
+        let input = format!("+---+\n| a|\n+---+\n{long}\n+---+\n| b|\n+---+\n{long}");
+        let (output, streamed) = correct_streamed(&Corrector::default(), &input);
+
+        assert_eq!(
+            output,
+            format!("+---+\n| a |\n+---+\n{long}\n+---+\n| b |\n+---+\n{long}")
+        );
+        assert_eq!(streamed.report.stats.total_lines, 8);
+        let starts: Vec<usize> = streamed
+            .report
+            .blocks
+            .iter()
+            .map(|b| b.block.start)
+            .collect();
+        assert_eq!(starts, [0, 4]);
+
+        let mut bad = "x".repeat(STREAM_MAX_LINE_BYTES * 2).into_bytes();
+        bad.push(0xFF);
+        let err = Corrector::default()
+            .correct_stream(&bad[..], &mut Vec::new())
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains(&format!("byte position {}", STREAM_MAX_LINE_BYTES * 2))
+        );
+    }
+
+    #[test]
+    fn test_correct_stream_rejects_invalid_input() {
+        let mut output = Vec::new();
+        let err = Corrector::default()
+            .correct_stream(&b"ok\nbad \xFF\n"[..], &mut output)
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("byte position 7"));
+
+        let err = Corrector::default()
+            .correct_stream(&b"a\0b"[..], &mut output)
+            .unwrap_err();
+        assert!(err.to_string().contains("binary"));
+    }
+
+    // =========================================================================
+    // Source comment tests
+    // =========================================================================
+
+    #[test]
+    fn test_find_comment_runs_groups_by_prefix() {
+        let lines = to_lines(
+            "fn main() {\n    // +--+\n    //\n    // |a|\n    let x = 1;\n# a\n#b\n/// doc\n",
+        );
+        let runs = find_comment_runs(&lines);
+        assert_eq!(
+            runs,
+            [
+                CommentRun {
+                    lines: 1..4,
+                    prefix: "    // ".to_string(),
+                },
+                CommentRun {
+                    lines: 5..7,
+                    prefix: "#".to_string(),
+                },
+                CommentRun {
+                    lines: 7..8,
+                    prefix: "/// ".to_string(),
+                },
+            ]
+        );
+        assert_eq!(runs[0].payload("    //"), "");
+        assert_eq!(runs[0].payload("    // aadc: off"), "// aadc: off");
+    }
+
+    #[test]
+    fn test_corrector_comments_only_touches_comments() {
+        let input = "+------+\n| hi|\n+------+\nfn f() {\n    // +------+\n    // | hi|\n    //\n    // +------+\n}\n";
+        let options = CorrectionOptions {
+            comments: true,
+            all_blocks: true,
+            ..Default::default()
+        };
+        let correction = Corrector::new(options).correct_str(input);
+        assert_eq!(
+            correction.output,
+            "+------+\n| hi|\n+------+\nfn f() {\n    // +------+\n    // | hi   |\n    //\n    // +------+\n}\n"
+        );
+        let result = correction.report.blocks[0].result.as_ref().unwrap();
+        assert_eq!(result.target_column, Some(14));
+        assert_eq!(result.revisions[0].revision.target_column(), 14);
+    }
+
+    #[test]
+    fn test_corrector_comments_block_comment_continuation() {
+        let input = "/*\n * +-----+\n * | a |\n * | bc|\n * +-----+\n */\n";
+        let options = CorrectionOptions {
+            comments: true,
+            ..Default::default()
+        };
+        let correction = Corrector::new(options).correct_str(input);
+        assert_eq!(
+            correction.output,
+            "/*\n * +-----+\n * | a   |\n * | bc  |\n * +-----+\n */\n"
+        );
+    }
+
+    #[test]
+    fn test_corrector_comments_honor_directives() {
+        let input = "# aadc: off\n# +------+\n# | hi|\n# +------+\n";
+        let options = CorrectionOptions {
+            comments: true,
+            all_blocks: true,
+            ..Default::default()
+        };
+        assert!(!Corrector::new(options).correct_str(input).changed);
+    }
+
+    #[test]
+    fn test_corrector_comments_honor_doc_and_block_directives() {
+        let options = CorrectionOptions {
+            comments: true,
+            all_blocks: true,
+            ..Default::default()
+        };
+        let corrector = Corrector::new(options);
+
+        let input = "/// aadc: off\n/// +------+\n/// | hi|\n/// +------+\n";
+        assert!(!corrector.correct_str(input).changed);
+
+        let input = "/*\n * aadc: off\n * +------+\n * | hi|\n * +------+\n */\n";
+        assert!(!corrector.correct_str(input).changed);
+    }
+
+    // =========================================================================
+    // Inline directive tests
+    // =========================================================================
+
+    #[test]
+    fn test_parse_directives_comment_styles() {
+        assert_eq!(parse_directives("<!-- aadc: off -->"), [Directive::Off]);
+        assert_eq!(parse_directives("  // aadc: on"), [Directive::On]);
+        assert_eq!(
+            parse_directives("# aadc: ignore-next-block"),
+            [Directive::IgnoreNextBlock]
+        );
+        assert_eq!(
+            parse_directives("-- aadc:force, min-score=0.3"),
+            [Directive::Force, Directive::MinScore(0.3)]
+        );
+        assert_eq!(parse_directives("/// aadc: off"), [Directive::Off]);
+        assert_eq!(parse_directives("//! aadc: on"), [Directive::On]);
+        assert_eq!(parse_directives(" * aadc: off"), [Directive::Off]);
+    }
+
+    #[test]
+    fn test_parse_directives_rejects_other_lines() {
+        assert!(parse_directives("aadc: off").is_empty(), "not a comment");
+        assert!(
+            parse_directives("# aadc: offf").is_empty(),
+            "unknown directive"
+        );
+        assert!(
+            parse_directives("# aadc: min-score=2").is_empty(),
+            "score out of range"
+        );
+        assert!(
+            parse_directives("<!-- aadc: off").is_empty(),
+            "unclosed comment"
+        );
+        assert!(
+            parse_directives("x = 1 // aadc: off").is_empty(),
+            "trailing comment"
+        );
+        assert!(parse_directives("# aadc:").is_empty());
+    }
+
+    #[test]
+    fn test_find_blocks_skips_off_regions() {
+        let lines = to_lines("# aadc: off\n+--+\n| a|\n+--+\n# aadc: on\n\n+--+\n| b|\n+--+");
+        let blocks = find_diagram_blocks(&lines, false);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!((blocks[0].start, blocks[0].end), (6, 9));
+    }
+
+    #[test]
+    fn test_find_blocks_next_block_directives() {
+        let lines = to_lines(
+            "// aadc: ignore-next-block\n+--+\n| a|\n+--+\n\n\n// aadc: force min-score=0.2\nprose\n| some text\n| more text",
+        );
+        let blocks = find_diagram_blocks(&lines, false);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!((blocks[0].start, blocks[0].end), (8, 10));
+        assert!(blocks[0].confidence < MIN_BLOCK_CONFIDENCE);
+        assert!(blocks[0].forced);
+        assert_eq!(blocks[0].min_score, Some(0.2));
+    }
+
+    #[test]
+    fn test_find_blocks_directive_ends_block() {
+        let lines = to_lines("+--+\n| a|\n+--+\n# aadc: ignore-next-block\n+--+\n| b|\n+--+");
+        let blocks = find_diagram_blocks(&lines, false);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!((blocks[0].start, blocks[0].end), (0, 3));
+    }
+
+    #[test]
+    fn test_corrector_markdown_directives_outside_fences() {
+        let options = CorrectionOptions {
+            markdown: true,
+            ..Default::default()
+        };
+        let input = "<!-- aadc: ignore-next-block -->\n```\n+------+\n| hi|\n+------+\n```\n\n```\n+------+\n| hi|\n+------+\n```\n";
+        let correction = Corrector::new(options).correct_str(input);
+        assert_eq!(
+            correction.output,
+            "<!-- aadc: ignore-next-block -->\n```\n+------+\n| hi|\n+------+\n```\n\n```\n+------+\n| hi   |\n+------+\n```\n"
+        );
+    }
+
+    #[test]
+    fn test_corrector_block_min_score_directive() {
+        // The fix scores 0.70: refused at 0.9, applied at the default 0.5
+        let input =
+            "# aadc: min-score=0.9\n+------+\n| hi|\n+------+\n\n\n+------+\n| hi|\n+------+\n";
+        let correction = Corrector::default().correct_str(input);
+        assert_eq!(
+            correction.output,
+            "# aadc: min-score=0.9\n+------+\n| hi|\n+------+\n\n\n+------+\n| hi   |\n+------+\n"
+        );
+    }
+
+    #[test]
+    fn test_corrector_force_bypasses_quick_scan() {
+        let mut input: String = (0..200).map(|i| format!("prose line {}\n", i)).collect();
+        input.push_str("# aadc: force\n| some text\n| more   text |\n");
+        let correction = Corrector::default().correct_str(&input);
+        assert!(correction.report.quick_scan.is_none());
+        assert!(
+            correction
+                .output
+                .ends_with("| some text   |\n| more   text |\n")
+        );
+    }
+
+    #[test]
+    fn test_correct_block_respects_min_score() {
+        let mut lines = vec![
+            "+------+".to_string(),
+            "| hi|".to_string(),
+            "+------+".to_string(),
+        ];
+        let block = DiagramBlock {
+            start: 0,
+            end: 3,
+            confidence: 1.0,
+            forced: false,
+            min_score: None,
+        };
+        let options = CorrectionOptions {
+            min_score: 0.95,
+            ..Default::default()
+        };
+
+        let result = correct_block(&mut lines, &block, &options);
+        assert_eq!(result.revisions_applied, 0);
+        assert_eq!(result.revisions_skipped, 1);
+        assert_eq!(lines[1], "| hi|");
+    }
+
+    #[test]
+    fn test_correct_block_records_revisions() {
+        let mut lines = vec![
+            "+------+".to_string(),
+            "| hi".to_string(),
+            "| hell|".to_string(),
+            "+------+".to_string(),
+        ];
+        let block = DiagramBlock {
+            start: 0,
+            end: 4,
+            confidence: 1.0,
+            forced: false,
+            min_score: None,
+        };
+
+        let result = correct_block(&mut lines, &block, &CorrectionOptions::default());
+        assert_eq!(result.border_char, Some('|'));
+        assert_eq!(result.target_column, Some(7));
+
+        let kinds: Vec<_> = result
+            .revisions
+            .iter()
+            .map(|r| (r.revision.kind(), r.revision.line_idx(), r.columns_added))
+            .collect();
+        assert_eq!(
+            kinds,
+            [("AddSuffixBorder", 1, 4), ("PadBeforeSuffixBorder", 2, 1)]
+        );
+        assert!(
+            result
+                .revisions
+                .iter()
+                .all(|r| r.applied && r.iteration == 0)
+        );
+        assert_eq!(lines[1], "| hi   |");
+    }
+}
@@ -5,7 +5,7 @@
 
 use std::fs;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use tempfile::TempDir;
 
@@ -136,6 +136,36 @@ fn run_aadc_args(args: &[&str]) -> (String, String, i32) {
     (stdout, stderr, code)
 }
 
+fn run_aadc_in(dir: &Path, args: &[&str]) -> (String, String, i32) {
+    test_log!("RUN", "aadc in {} with args: {:?}", dir.display(), args);
+
+    let output = Command::new(get_binary_path())
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("Failed to run aadc");
+
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    let code = output.status.code().unwrap_or(-1);
+
+    test_log!("OUTPUT", "Exit code: {}", code);
+
+    (stdout, stderr, code)
+}
+
+/// Run git in `dir`, panicking if it fails
+fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .args(["-c", "user.name=aadc", "-c", "user.email=aadc@example.com"])
+        .args(args)
+        .current_dir(dir)
+        .stdout(Stdio::null())
+        .status()
+        .expect("Failed to run git");
+    assert!(status.success(), "git {:?} failed", args);
+}
+
 // ============================================================================
 // Basic Functionality Tests
 // ============================================================================
@@ -263,6 +293,29 @@ fn test_e2e_min_score_threshold() {
     test_log!("END", "Test PASSED");
 }
 
+#[test]
+fn test_e2e_quick_scan_finds_diagram_deep_in_file() {
+    test_log!(
+        "START",
+        "A diagram after thousands of prose lines is still corrected"
+    );
+
+    let mut input: String = (0..3000)
+        .map(|i| format!("Changelog entry {i}\n"))
+        .collect();
+    input.push_str("+------+\n| hi|\n+------+\n");
+
+    let (stdout, _stderr, code) = run_aadc_stdin(&input, &[]);
+    assert_eq!(code, 0, "Should exit successfully");
+    assert!(stdout.ends_with("+------+\n| hi   |\n+------+\n"));
+
+    // Verbose output names the region that triggered processing
+    let (stdout, _stderr, _code) = run_aadc_stdin(&input, &["-v"]);
+    assert!(stdout.contains("1 candidate region(s) at lines 3001-3003"));
+
+    test_log!("END", "Test PASSED");
+}
+
 // ============================================================================
 // Recursive Mode Tests
 // ============================================================================
@@ -276,7 +329,7 @@ fn test_e2e_recursive_in_place() {
     let nested = root.join("nested");
     fs::create_dir_all(&nested).unwrap();
 
-    let input = "+---+\n| a|\n+---+\n";
+    let input = "```text\n+---+\n| a|\n+---+\n```\n";
     fs::write(root.join("a.md"), input).unwrap();
     fs::write(nested.join("b.md"), input).unwrap();
 
@@ -293,6 +346,506 @@ fn test_e2e_recursive_in_place() {
     test_log!("END", "Test PASSED");
 }
 
+#[test]
+fn test_e2e_recursive_jobs_output_is_deterministic() {
+    test_log!(
+        "START",
+        "Parallel recursive output matches sequential output"
+    );
+
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    for i in 0..24 {
+        let padding = "x".repeat(i);
+        let input = format!("+----{padding}+\n| {i}|\n+----{padding}+\n");
+        fs::write(root.join(format!("f{i:02}.txt")), input).unwrap();
+    }
+
+    let dir_arg = root.to_str().unwrap();
+    let (sequential, _stderr, code) = run_aadc_args(&["-r", "--json", dir_arg]);
+    assert_eq!(code, 0, "Sequential run should succeed");
+    let (parallel, _stderr, code) = run_aadc_args(&["-r", "--json", "-j", "4", dir_arg]);
+    assert_eq!(code, 0, "Parallel run should succeed");
+    assert_eq!(parallel, sequential);
+
+    let (sequential, _stderr, _code) = run_aadc_args(&["-r", dir_arg]);
+    let (parallel, _stderr, _code) = run_aadc_args(&["-r", "--jobs", "0", dir_arg]);
+    assert_eq!(parallel, sequential);
+    assert!(sequential.find("f00.txt").unwrap() < sequential.find("f23.txt").unwrap());
+
+    test_log!("END", "Test PASSED");
+}
+
+#[test]
+fn test_e2e_in_place_skips_unchanged_files() {
+    test_log!("START", "In-place mode leaves unchanged files untouched");
+
+    let temp = TempDir::new().unwrap();
+    let clean = temp.path().join("clean.txt");
+    let messy = temp.path().join("messy.txt");
+    fs::write(&clean, "+---+\n| a |\n+---+\n").unwrap();
+    fs::write(&messy, "+---+\n| a|\n+---+\n").unwrap();
+    let old_mtime = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+    for path in [&clean, &messy] {
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(old_mtime)
+            .unwrap();
+    }
+
+    let (_stdout, _stderr, code) = run_aadc_args(&[
+        "-i",
+        "--backup",
+        "--preserve-mtime",
+        clean.to_str().unwrap(),
+        messy.to_str().unwrap(),
+    ]);
+    assert_eq!(code, 0, "Should exit successfully");
+
+    assert_eq!(fs::read_to_string(&messy).unwrap(), "+---+\n| a |\n+---+\n");
+    assert_eq!(fs::metadata(&messy).unwrap().modified().unwrap(), old_mtime);
+    assert!(temp.path().join("messy.txt.bak").exists());
+    assert!(
+        !temp.path().join("clean.txt.bak").exists(),
+        "No backup for a file that was not rewritten"
+    );
+    assert_eq!(fs::metadata(&clean).unwrap().modified().unwrap(), old_mtime);
+
+    test_log!("END", "Test PASSED");
+}
+
+#[test]
+fn test_e2e_in_place_preserves_line_endings_and_bom() {
+    test_log!(
+        "START",
+        "In-place keeps CRLF, BOM and missing final newline"
+    );
+
+    let temp = TempDir::new().unwrap();
+    let path = temp.path().join("crlf.txt");
+    fs::write(&path, "\u{FEFF}+---+\r\n| a|\r\n+---+").unwrap();
+
+    let (_stdout, _stderr, code) = run_aadc_file(path.to_str().unwrap(), &["-i"]);
+    assert_eq!(code, 0, "Should exit successfully");
+
+    let contents = fs::read_to_string(&path).unwrap();
+    assert_eq!(contents, "\u{FEFF}+---+\r\n| a |\r\n+---+");
+
+    test_log!("END", "Test PASSED");
+}
+
+#[test]
+fn test_e2e_mixed_line_endings_are_kept_per_line() {
+    test_log!("START", "Each line keeps its own terminator");
+
+    let temp = TempDir::new().unwrap();
+    let path = temp.path().join("mixed.txt");
+    fs::write(&path, "intro\r\n+------+\n| ab |\n+------+\n").unwrap();
+
+    let (_stdout, _stderr, code) = run_aadc_file(path.to_str().unwrap(), &["-i"]);
+    assert_eq!(code, 0, "Should exit successfully");
+
+    let contents = fs::read_to_string(&path).unwrap();
+    assert_eq!(contents, "intro\r\n+------+\n| ab   |\n+------+\n");
+
+    let (stdout, _stderr, code) = run_aadc_stdin("x\r\ny\r\nz\n", &[]);
+    assert_eq!(code, 0, "Should exit successfully");
+    assert_eq!(stdout, "x\r\ny\r\nz\n");
+
+    test_log!("END", "Test PASSED");
+}
+
+#[test]
+fn test_e2e_stream_stdin() {
+    test_log!("START", "Stream mode corrects stdin as it reads");
+
+    let filler = "log line\n".repeat(5000);
+    let input = format!("{filler}+---+\r\n| a|\r\n+---+\r\n{filler}+----+\n| ab |\n| a|\n+----+");
+    let (stdout, _stderr, code) = run_aadc_stdin(&input, &["--stream"]);
+    assert_eq!(code, 0, "Should exit successfully");
+    assert_eq!(
+        stdout,
+        format!("{filler}+---+\r\n| a |\r\n+---+\r\n{filler}+----+\n| ab |\n| a  |\n+----+")
+    );
+
+    let (_stdout, _stderr, code) = run_aadc_stdin(&input, &["--stream", "-n"]);
+    assert_eq!(code, 3, "Dry run should report that changes would be made");
+
+    let (_stdout, stderr, code) = run_aadc_stdin("+---+\0| a |\n", &["--stream"]);
+    assert_eq!(code, 4, "Binary input is a parse error");
+    assert!(stderr.contains("binary"), "Should mention binary: {stderr}");
+
+    test_log!("END", "Test PASSED");
+}
+
+#[test]
+fn test_e2e_stream_in_place() {
+    test_log!("START", "Stream mode edits files in place");
+
+    let temp = TempDir::new().unwrap();
+    let messy = temp.path().join("messy.txt");
+    let clean = temp.path().join("clean.txt");
+    fs::write(&messy, "intro\n+---+\n| a|\n+---+\n").unwrap();
+    fs::write(&clean, "plain text\n").unwrap();
+
+    let (_stdout, _stderr, code) = run_aadc_args(&[
+        "--stream",
+        "-i",
+        "--backup",
+        messy.to_str().unwrap(),
+        clean.to_str().unwrap(),
+    ]);
+    assert_eq!(code, 0, "Should exit successfully");
+    assert_eq!(
+        fs::read_to_string(&messy).unwrap(),
+        "intro\n+---+\n| a |\n+---+\n"
+    );
+    assert_eq!(
+        fs::read_to_string(temp.path().join("messy.txt.bak")).unwrap(),
+        "intro\n+---+\n| a|\n+---+\n"
+    );
+    assert!(
+        !temp.path().join("clean.txt.bak").exists(),
+        "No backup for a file that was not rewritten"
+    );
+    assert_eq!(fs::read_to_string(&clean).unwrap(), "plain text\n");
+
+    test_log!("END", "Test PASSED");
+}
+
+#[test]
+fn test_e2e_stream_refuses_markdown() {
+    test_log!("START", "Stream mode refuses inputs it would misread");
+
+    let temp = TempDir::new().unwrap();
+    let doc = temp.path().join("u.md");
+    let notes = temp.path().join("notes.txt");
+    fs::write(&doc, "+---+\n| a|\n+---+\n").unwrap();
+    fs::write(&notes, "+---+\n| a|\n+---+\n").unwrap();
+
+    let (_stdout, stderr, code) = run_aadc_args(&[
+        "--stream",
+        "-i",
+        notes.to_str().unwrap(),
+        doc.to_str().unwrap(),
+    ]);
+    assert_eq!(code, 2, "Markdown input is refused");
+    assert!(
+        stderr.contains("Markdown"),
+        "Should name the mode: {stderr}"
+    );
+    assert_eq!(
+        fs::read_to_string(&notes).unwrap(),
+        "+---+\n| a|\n+---+\n",
+        "Nothing is written when any input is refused"
+    );
+    assert_eq!(fs::read_to_string(&doc).unwrap(), "+---+\n| a|\n+---+\n");
+
+    let (_stdout, _stderr, code) = run_aadc_stdin("x\n", &["--stream", "--markdown", "always"]);
+    assert_eq!(code, 2, "--markdown conflicts with --stream");
+    let (_stdout, _stderr, code) = run_aadc_stdin("x\n", &["--stream", "--comments"]);
+    assert_eq!(code, 2, "--comments conflicts with --stream");
+
+    test_log!("END", "Test PASSED");
+}
+
+#[test]
+fn test_e2e_changed_only_touches_edited_diagrams() {
+    test_log!("START", "--changed corrects only diagrams with git changes");
+
+    let temp = TempDir::new().unwrap();
+    let repo = temp.path();
+    git(repo, &["init", "-q"]);
+    let legacy = "Legacy:\n+---+\n| a|\n+---+\n\n";
+    fs::write(
+        repo.join("doc.txt"),
+        format!("{legacy}New:\n+---+\n| b |\n+---+\n"),
+    )
+    .unwrap();
+    fs::write(repo.join("other.txt"), "+---+\n| c|\n+---+\n").unwrap();
+    git(repo, &["add", "."]);
+    git(repo, &["commit", "-q", "-m", "init"]);
+
+    // Edit only the second diagram of doc.txt, and add an untracked file
+    let edited = format!("{legacy}New:\n+----+\n| bb |\n| b|\n+----+\n");
+    fs::write(repo.join("doc.txt"), &edited).unwrap();
+    fs::create_dir(repo.join("docs")).unwrap();
+    fs::write(repo.join("docs/fresh.txt"), "+---+\n| d|\n+---+\n").unwrap();
+
+    let (_stdout, _stderr, code) = run_aadc_in(repo, &["--changed", "--dry-run"]);
+    assert_eq!(code, 3, "Dry run should report that changes would be made");
+    assert_eq!(fs::read_to_string(repo.join("doc.txt")).unwrap(), edited);
+
+    let (_stdout, stderr, code) = run_aadc_in(repo, &["--changed", "-i"]);
+    assert_eq!(code, 0, "Should exit successfully: {stderr}");
+    assert_eq!(
+        fs::read_to_string(repo.join("doc.txt")).unwrap(),
+        format!("{legacy}New:\n+----+\n| bb |\n| b  |\n+----+\n"),
+        "The legacy diagram is left alone"
+    );
+    assert_eq!(
+        fs::read_to_string(repo.join("docs/fresh.txt")).unwrap(),
+        "+---+\n| d |\n+---+\n"
+    );
+    assert_eq!(
+        fs::read_to_string(repo.join("other.txt")).unwrap(),
+        "+---+\n| c|\n+---+\n",
+        "Unchanged files are not touched"
+    );
+
+    // Against the first commit, with the inputs limiting the selection
+    git(repo, &["add", "."]);
+    git(repo, &["commit", "-q", "-m", "edit"]);
+    fs::write(repo.join("other.txt"), "+---+\n| c|\n+---+\nmore\n").unwrap();
+    let (stdout, _stderr, code) = run_aadc_in(repo, &["--since", "HEAD~1", "docs"]);
+    assert_eq!(code, 0, "Should exit successfully");
+    assert_eq!(stdout, "+---+\n| d |\n+---+\n");
+
+    let (_stdout, stderr, code) = run_aadc_in(repo, &["--since", "no-such-rev"]);
+    assert_eq!(code, 2, "An unknown revision is a usage error");
+    assert!(
+        stderr.contains("no-such-rev"),
+        "Should name the revision: {stderr}"
+    );
+
+    test_log!("END", "Test PASSED");
+}
+
+#[test]
+fn test_e2e_staged_only_touches_staged_diagrams() {
+    test_log!(
+        "START",
+        "--staged corrects only diagrams changed in the index"
+    );
+
+    let temp = TempDir::new().unwrap();
+    let repo = temp.path();
+    git(repo, &["init", "-q"]);
+    let first = "A:\n+---+\n| a|\n+---+\n\n";
+    fs::write(
+        repo.join("doc.txt"),
+        format!("{first}B:\n+---+\n| b |\n+---+\n"),
+    )
+    .unwrap();
+    git(repo, &["add", "."]);
+    git(repo, &["commit", "-q", "-m", "init"]);
+
+    // Stage an edit to diagram B, then insert unstaged lines above both
+    fs::write(
+        repo.join("doc.txt"),
+        format!("{first}B:\n+----+\n| b|\n+----+\n"),
+    )
+    .unwrap();
+    git(repo, &["add", "doc.txt"]);
+    let worktree = format!("x\ny\nz\n{first}B:\n+----+\n| b|\n+----+\n");
+    fs::write(repo.join("doc.txt"), &worktree).unwrap();
+    fs::write(repo.join("untracked.txt"), "+---+\n| u|\n+---+\n").unwrap();
+
+    let (stdout, stderr, code) = run_aadc_in(repo, &["--staged"]);
+    assert_eq!(code, 0, "Should exit successfully: {stderr}");
+    assert_eq!(
+        stdout,
+        format!("x\ny\nz\n{first}B:\n+----+\n| b  |\n+----+\n"),
+        "Only the staged diagram is corrected, at its working-copy lines"
+    );
+
+    test_log!("END", "Test PASSED");
+}
+
+#[test]
+fn test_e2e_changed_with_non_utf8_changes() {
+    test_log!("START", "--changed copes with non-UTF-8 files in the diff");
+
+    let temp = TempDir::new().unwrap();
+    let repo = temp.path();
+    git(repo, &["init", "-q"]);
+    fs::write(repo.join("latin1.dat"), b"caf\xe9\n").unwrap();
+    fs::write(repo.join("doc.txt"), "intro\n").unwrap();
+    git(repo, &["add", "."]);
+    git(repo, &["commit", "-q", "-m", "init"]);
+
+    fs::write(repo.join("latin1.dat"), b"caf\xe9\nna\xefve\n").unwrap();
+    fs::write(repo.join("doc.txt"), "intro\n+---+\n| a|\n+---+\n").unwrap();
+
+    let (stdout, stderr, code) = run_aadc_in(repo, &["--changed"]);
+    assert_eq!(code, 0, "Should exit successfully: {stderr}");
+    assert_eq!(stdout, "intro\n+---+\n| a |\n+---+\n");
+
+    test_log!("END", "Test PASSED");
+}
+
+#[test]
+fn test_e2e_hook_run_checks_staged_content() {
+    test_log!("START", "hook run checks and fixes what is staged");
+
+    let temp = TempDir::new().unwrap();
+    let repo = temp.path();
+    git(repo, &["init", "-q"]);
+    let messy = "+---+\n| a|\n+---+\n";
+    let fixed = "+---+\n| a |\n+---+\n";
+    fs::write(repo.join("doc.txt"), messy).unwrap();
+    // Not matched by *.txt, although it contains "txt"
+    fs::write(repo.join("doc.txt.orig"), messy).unwrap();
+    git(repo, &["add", "."]);
+
+    // The working tree is fixed, but the commit would not be
+    fs::write(repo.join("doc.txt"), fixed).unwrap();
+    let (stdout, _stderr, code) = run_aadc_in(repo, &["hook", "run", "--patterns", "*.txt"]);
+    assert_eq!(code, 3, "Staged content needs fixing: {stdout}");
+    assert!(stdout.contains("doc.txt"), "Should name the file: {stdout}");
+    assert!(
+        !stdout.contains("doc.txt.orig"),
+        "Glob must not match: {stdout}"
+    );
+
+    fs::write(repo.join("doc.txt"), messy).unwrap();
+    let (stdout, stderr, code) = run_aadc_in(repo, &["hook", "run", "--auto-fix"]);
+    assert_eq!(code, 0, "Auto-fix should succeed: {stderr}");
+    assert!(
+        stdout.contains("Auto-fixed"),
+        "Should report the fix: {stdout}"
+    );
+    assert_eq!(fs::read_to_string(repo.join("doc.txt")).unwrap(), fixed);
+    let staged = Command::new("git")
+        .args(["show", ":doc.txt"])
+        .current_dir(repo)
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&staged.stdout), fixed);
+    assert_eq!(
+        fs::read_to_string(repo.join("doc.txt.orig")).unwrap(),
+        messy
+    );
+
+    let (_stdout, _stderr, code) = run_aadc_in(repo, &["hook", "run"]);
+    assert_eq!(code, 0, "Nothing left to fix");
+
+    test_log!("END", "Test PASSED");
+}
+
+#[test]
+fn test_e2e_hook_auto_fix_keeps_unstaged_changes() {
+    test_log!(
+        "START",
+        "hook auto-fix leaves unstaged edits out of the commit"
+    );
+
+    let temp = TempDir::new().unwrap();
+    let repo = temp.path();
+    git(repo, &["init", "-q"]);
+    let staged = "intro\n+---+\n| a|\n+---+\nend\n";
+    fs::write(repo.join("doc.txt"), staged).unwrap();
+    git(repo, &["add", "doc.txt"]);
+    fs::write(repo.join("doc.txt"), format!("{staged}unstaged\n")).unwrap();
+
+    let (stdout, stderr, code) = run_aadc_in(repo, &["hook", "run", "--auto-fix"]);
+    assert_eq!(code, 0, "The fix applies cleanly: {stdout}{stderr}");
+    let index = Command::new("git")
+        .args(["show", ":doc.txt"])
+        .current_dir(repo)
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&index.stdout),
+        "intro\n+---+\n| a |\n+---+\nend\n",
+        "Only the staged version goes into the index"
+    );
+    assert_eq!(
+        fs::read_to_string(repo.join("doc.txt")).unwrap(),
+        "intro\n+---+\n| a |\n+---+\nend\nunstaged\n",
+        "The working copy gets the same fix and keeps its edits"
+    );
+
+    // An unstaged edit to the diagram itself: refuse, and touch nothing,
+    // not even a file before it that could be fixed on its own
+    let messy = "+---+\n| z|\n+---+\n";
+    fs::write(repo.join("a.txt"), messy).unwrap();
+    fs::write(repo.join("doc.txt"), staged).unwrap();
+    git(repo, &["add", "a.txt", "doc.txt"]);
+    let edited = "intro\n+---+\n| a|\n| bb |\n+---+\nend\n";
+    fs::write(repo.join("doc.txt"), edited).unwrap();
+    let (stdout, _stderr, code) = run_aadc_in(repo, &["hook", "run", "--auto-fix"]);
+    assert_eq!(code, 3, "Should block the commit: {stdout}");
+    assert!(
+        stdout.contains("unstaged changes"),
+        "Should explain: {stdout}"
+    );
+    assert!(
+        stdout.contains("No files were auto-fixed"),
+        "Should say nothing was fixed: {stdout}"
+    );
+    assert_eq!(fs::read_to_string(repo.join("doc.txt")).unwrap(), edited);
+    assert_eq!(fs::read_to_string(repo.join("a.txt")).unwrap(), messy);
+    for (path, expected) in [(":doc.txt", staged), (":a.txt", messy)] {
+        let index = Command::new("git")
+            .args(["show", path])
+            .current_dir(repo)
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&index.stdout), expected);
+    }
+
+    test_log!("END", "Test PASSED");
+}
+
+#[test]
+fn test_e2e_markdown_only_corrects_fences() {
+    test_log!("START", "Markdown files only correct fenced code blocks");
+
+    let temp = TempDir::new().unwrap();
+    let path = temp.path().join("doc.md");
+    let input = "| Col | Other |\n|-----|---|\n\n```text\n+---+\n| a|\n+---+\n```\n";
+    fs::write(&path, input).unwrap();
+
+    let (stdout, _stderr, code) = run_aadc_args(&[path.to_str().unwrap()]);
+    assert_eq!(code, 0, "Should exit successfully");
+    assert_eq!(
+        stdout,
+        "| Col | Other |\n|-----|---|\n\n```text\n+---+\n| a |\n+---+\n```\n"
+    );
+
+    let (stdout, _stderr, code) = run_aadc_args(&["--markdown", "never", path.to_str().unwrap()]);
+    assert_eq!(code, 0, "Should exit successfully");
+    assert!(
+        stdout.starts_with("| Col | Other |\n|-----|-------|"),
+        "Without Markdown mode the table is treated as a diagram: {stdout}"
+    );
+
+    test_log!("END", "Test PASSED");
+}
+
+#[test]
+fn test_e2e_comments_recursive_corrects_source_comments() {
+    test_log!(
+        "START",
+        "Comment mode finds source files and corrects their comments"
+    );
+
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    let source =
+        "fn main() {\n    // +------+\n    // | hi|\n    // +------+\n    let s = \"| x|\";\n}\n";
+    fs::write(root.join("main.rs"), source).unwrap();
+    fs::write(root.join("script.py"), "# +----+\n# | a|\n# +----+\n").unwrap();
+
+    let dir_arg = root.to_str().unwrap();
+    let (_stdout, _stderr, code) = run_aadc_args(&["-r", "-i", "--comments", "--all", dir_arg]);
+    assert_eq!(code, 0, "Should exit successfully");
+
+    assert_eq!(
+        fs::read_to_string(root.join("main.rs")).unwrap(),
+        "fn main() {\n    // +------+\n    // | hi   |\n    // +------+\n    let s = \"| x|\";\n}\n"
+    );
+    assert_eq!(
+        fs::read_to_string(root.join("script.py")).unwrap(),
+        "# +----+\n# | a  |\n# +----+\n"
+    );
+
+    test_log!("END", "Test PASSED");
+}
+
 #[test]
 fn test_e2e_recursive_respects_gitignore() {
     test_log!("START", "Recursive mode respects .gitignore by default");
@@ -302,7 +855,7 @@ fn test_e2e_recursive_respects_gitignore() {
     fs::create_dir(root.join(".git")).unwrap();
     fs::write(root.join(".gitignore"), "ignored.md\n").unwrap();
 
-    let input = "+---+\n| a|\n+---+\n";
+    let input = "```text\n+---+\n| a|\n+---+\n```\n";
     fs::write(root.join("included.md"), input).unwrap();
     fs::write(root.join("ignored.md"), input).unwrap();
 
@@ -622,6 +1175,251 @@ fn test_e2e_multiple_files() {
 // Error Handling Tests (from bd-b9s)
 // ============================================================================
 
+#[test]
+fn test_e2e_multiple_files_json_envelope() {
+    test_log!("START", "Multiple files produce one JSON document");
+
+    let temp = TempDir::new().unwrap();
+    let a = temp.path().join("a.txt");
+    let b = temp.path().join("b.txt");
+    fs::write(&a, "+---+\n| a|\n+---+\n").unwrap();
+    fs::write(&b, "plain text\n").unwrap();
+
+    let (stdout, _stderr, code) =
+        run_aadc_args(&["--json", a.to_str().unwrap(), b.to_str().unwrap()]);
+    assert_eq!(code, 0, "Should exit successfully");
+
+    let json: serde_json::Value = serde_json::from_str(&stdout).expect("one JSON document");
+    assert_eq!(json["status"], "success");
+    assert_eq!(json["files"].as_array().unwrap().len(), 2);
+    assert_eq!(json["files"][0]["output"]["changed"], true);
+    assert_eq!(json["summary"]["files_processed"], 2);
+    assert_eq!(json["summary"]["files_changed"], 1);
+    assert_eq!(json["summary"]["revisions_applied"], 1);
+
+    test_log!("END", "Test PASSED");
+}
+
+#[test]
+fn test_e2e_json_lines_streams_files_and_errors() {
+    test_log!(
+        "START",
+        "--json-lines emits one object per file, errors included"
+    );
+
+    let temp = TempDir::new().unwrap();
+    let a = temp.path().join("a.txt");
+    let bad = temp.path().join("bad.txt");
+    fs::write(&a, "+---+\n| a|\n+---+\n").unwrap();
+    fs::write(&bad, [0xFF, 0xFE]).unwrap();
+
+    let (stdout, _stderr, code) =
+        run_aadc_args(&["--json-lines", a.to_str().unwrap(), bad.to_str().unwrap()]);
+    assert_eq!(code, 4, "Parse errors should still fail the run");
+
+    let lines: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("each line is JSON"))
+        .collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["status"], "success");
+    assert_eq!(lines[1]["status"], "error");
+    assert!(
+        lines[1]["error"]
+            .as_str()
+            .unwrap()
+            .contains("Invalid UTF-8")
+    );
+
+    test_log!("END", "Test PASSED");
+}
+
+#[test]
+fn test_e2e_lint_reports_without_writing() {
+    test_log!("START", "lint reports findings and leaves files alone");
+
+    let temp = TempDir::new().unwrap();
+    let path = temp.path().join("box.txt");
+    let input = "+------+\n| hi|\n+------+\n";
+    fs::write(&path, input).unwrap();
+    let file = path.to_str().unwrap();
+
+    let (stdout, _stderr, code) = run_aadc_args(&["lint", file]);
+    assert_eq!(code, 3, "Findings should exit with 3");
+    assert_eq!(
+        stdout,
+        format!(
+            "{}:2:5: right border at column 5, expected column 8\n",
+            file
+        )
+    );
+    assert_eq!(fs::read_to_string(&path).unwrap(), input);
+
+    let (stdout, _stderr, code) = run_aadc_args(&["lint", "--format", "github", file]);
+    assert_eq!(code, 3);
+    assert!(stdout.starts_with("::warning file="));
+
+    fs::write(&path, "+------+\n| hi   |\n+------+\n").unwrap();
+    let (stdout, _stderr, code) = run_aadc_args(&["lint", file]);
+    assert_eq!(code, 0, "Clean files should exit with 0");
+    assert!(stdout.is_empty());
+
+    test_log!("END", "Test PASSED");
+}
+
+#[test]
+fn test_e2e_review_writes_only_accepted_blocks() {
+    test_log!("START", "--review applies the blocks the user accepts");
+
+    let temp = TempDir::new().unwrap();
+    let path = temp.path().join("two.txt");
+    fs::write(
+        &path,
+        "+------+\n| hi|\n+------+\n\nSome prose between the diagrams.\n\n+------+\n| yo|\n+------+\n",
+    )
+    .unwrap();
+
+    let (stdout, stderr, code) = run_aadc_stdin(
+        "n\ny\n",
+        &["--review", "--color", "never", path.to_str().unwrap()],
+    );
+    assert_eq!(code, 0, "review should succeed: {}", stderr);
+    assert!(
+        stdout.contains("-| hi|\n+| hi   |\n"),
+        "shows a diff: {}",
+        stdout
+    );
+    assert!(stdout.contains("Applied 1 of 2 change(s)"));
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "+------+\n| hi|\n+------+\n\nSome prose between the diagrams.\n\n+------+\n| yo   |\n+------+\n"
+    );
+
+    // No answers (stdin at end of input) writes nothing
+    let before = fs::read_to_string(&path).unwrap();
+    let (_stdout, _stderr, code) =
+        run_aadc_stdin("", &["--review", "--backup", path.to_str().unwrap()]);
+    assert_eq!(code, 0);
+    assert_eq!(fs::read_to_string(&path).unwrap(), before);
+    assert!(!temp.path().join("two.txt.bak").exists());
+
+    test_log!("END", "Test PASSED");
+}
+
+#[test]
+fn test_e2e_explain_traces_to_stderr() {
+    test_log!("START", "--explain writes its trace to stderr only");
+
+    let input = "+------+\n| hi|\n+------+\n";
+    let (plain, _stderr, _code) = run_aadc_stdin(input, &[]);
+    let (stdout, stderr, code) = run_aadc_stdin(input, &["--explain"]);
+    assert_eq!(code, 0);
+    assert_eq!(stdout, plain, "output is unchanged by --explain");
+    assert!(stderr.starts_with("explain: stdin\n"), "{}", stderr);
+    assert!(stderr.contains("      2  strong  border at both ends, 2/5 box chars >= 1/3\n"));
+    assert!(stderr.contains("-> applied (iteration 1)\n"));
+
+    test_log!("END", "Test PASSED");
+}
+
+/// Frame LSP messages with Content-Length headers
+fn lsp_frames(messages: &[serde_json::Value]) -> String {
+    messages
+        .iter()
+        .map(|message| {
+            let body = message.to_string();
+            format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+        })
+        .collect()
+}
+
+#[test]
+fn test_e2e_lsp_honors_aadcrc_next_to_document() {
+    test_log!(
+        "START",
+        "aadc lsp reads .aadcrc from the document's directory"
+    );
+
+    let temp = TempDir::new().unwrap();
+    let plain = temp.path().join("plain");
+    let markdown = temp.path().join("markdown");
+    fs::create_dir(&plain).unwrap();
+    fs::create_dir(&markdown).unwrap();
+    // Outside a fence, so Markdown mode leaves the box alone
+    fs::write(markdown.join(".aadcrc"), "markdown = \"always\"\n").unwrap();
+
+    let text = "+------+\n| hi|\n+------+\n";
+    let open = |dir: &Path| {
+        let uri = format!("file://{}", dir.join("box.txt").display());
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": uri, "languageId": "plaintext", "version": 1, "text": text } },
+        })
+    };
+    let input = lsp_frames(&[
+        serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+        open(&plain),
+        open(&markdown),
+        serde_json::json!({ "jsonrpc": "2.0", "id": 2, "method": "shutdown" }),
+        serde_json::json!({ "jsonrpc": "2.0", "method": "exit" }),
+    ]);
+
+    let (stdout, stderr, code) = run_aadc_stdin(&input, &["lsp", "--stdio"]);
+    assert_eq!(code, 0, "exit after shutdown should succeed: {}", stderr);
+
+    let counts: Vec<_> = stdout
+        .split("Content-Length: ")
+        .filter_map(|frame| frame.split_once("\r\n\r\n"))
+        .map(|(_, body)| serde_json::from_str::<serde_json::Value>(body).unwrap())
+        .filter(|message| message["method"] == "textDocument/publishDiagnostics")
+        .map(|message| message["params"]["diagnostics"].as_array().unwrap().len())
+        .collect();
+    assert_eq!(counts, [1, 0]);
+
+    test_log!("END", "Test PASSED");
+}
+
+#[test]
+fn test_e2e_sarif_across_multiple_files() {
+    test_log!("START", "--format sarif collects every file into one log");
+
+    let temp = TempDir::new().unwrap();
+    let a = temp.path().join("a.txt");
+    let b = temp.path().join("b.txt");
+    let input = "+------+\n| hi|\n+------+\n";
+    fs::write(&a, input).unwrap();
+    fs::write(&b, input).unwrap();
+
+    let (stdout, _stderr, code) = run_aadc_args(&[
+        "--format",
+        "sarif",
+        a.to_str().unwrap(),
+        b.to_str().unwrap(),
+    ]);
+    assert_eq!(code, 3, "Proposed changes exit like --dry-run");
+
+    let sarif: serde_json::Value = serde_json::from_str(&stdout).expect("one SARIF log");
+    let results = sarif["runs"][0]["results"].as_array().unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(
+        results[0]["properties"]["revision"],
+        "PadBeforeSuffixBorder"
+    );
+    assert_eq!(
+        fs::read_to_string(&a).unwrap(),
+        input,
+        "Files are not written"
+    );
+
+    let (stdout, _stderr, code) = run_aadc_args(&["--format", "github", a.to_str().unwrap()]);
+    assert_eq!(code, 3);
+    assert!(stdout.starts_with("::warning file="));
+    assert!(stdout.contains(",line=2,col=5,"));
+
+    test_log!("END", "Test PASSED");
+}
+
 #[test]
 fn test_e2e_exit_code_invalid_tab_width_zero() {
     test_log!("START", "Exit code 2 for invalid tab width (0)");
@@ -709,3 +1507,70 @@ fn test_e2e_binary_file_detection() {
 
     test_log!("END", "Test PASSED");
 }
+
+// ============================================================================
+// Fixture Tests (the pairs checked by tests/e2e_fixtures.sh)
+// ============================================================================
+
+/// All `*.input.txt` files under `dir`, sorted
+fn fixture_inputs(dir: &Path) -> Vec<PathBuf> {
+    let mut inputs = Vec::new();
+    for entry in fs::read_dir(dir).expect("Failed to read fixtures directory") {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            inputs.extend(fixture_inputs(&path));
+        } else if path.to_string_lossy().ends_with(".input.txt") {
+            inputs.push(path);
+        }
+    }
+    inputs.sort();
+    inputs
+}
+
+#[test]
+fn test_e2e_fixtures() {
+    test_log!("START", "Every fixture input produces its expected output");
+
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let inputs = fixture_inputs(&fixtures);
+    assert!(
+        !inputs.is_empty(),
+        "No fixtures found in {}",
+        fixtures.display()
+    );
+
+    let mut failures = Vec::new();
+    for input in &inputs {
+        let name = input.strip_prefix(&fixtures).unwrap().display().to_string();
+        let expected_path = PathBuf::from(
+            input
+                .to_string_lossy()
+                .replace(".input.txt", ".expected.txt"),
+        );
+        let expected = fs::read_to_string(&expected_path)
+            .unwrap_or_else(|_| panic!("Missing expected file for {name}"));
+
+        let (stdout, stderr, code) = run_aadc_file(input.to_str().unwrap(), &[]);
+        test_log!("FIXTURE", "{} -> exit {}", name, code);
+        if code != 0 || stdout != expected {
+            failures.push(format!(
+                "{name} (exit {code}){}\n--- expected\n{expected}--- got\n{stdout}",
+                if stderr.is_empty() {
+                    String::new()
+                } else {
+                    format!(": {stderr}")
+                }
+            ));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} fixture(s) failed:\n{}",
+        failures.len(),
+        inputs.len(),
+        failures.join("\n")
+    );
+
+    test_log!("END", "Test PASSED");
+}